@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use serde_json;
+
+use super::{Pos2D, TetrisBoard, TetrisPiece};
+
+// A copy of the board's cells that placements can be tried out on.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Field {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<bool>>,
+}
+
+impl Field {
+    pub fn from_board(board: &TetrisBoard) -> Self {
+        Field {
+            width: board.width,
+            height: board.height,
+            cells: board.board.iter()
+                .map(|row| row.iter().map(|block| block.is_filled).collect())
+                .collect(),
+        }
+    }
+
+    // An empty board of the given size, walls included.
+    pub fn empty(width: usize, height: usize) -> Self {
+        let cells = (0..height)
+            .map(|y| (0..width).map(|x| x == 0 || x == width - 1 || y == 0 || y == height - 1).collect())
+            .collect();
+        Field {
+            width: width,
+            height: height,
+            cells: cells,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        self.cells[y][x]
+    }
+
+    pub fn fill(&mut self, x: usize, y: usize) {
+        self.cells[y][x] = true;
+    }
+
+    pub fn fits(&self, piece: &TetrisPiece) -> bool {
+        piece.iter().all(|pos| {
+            pos.x >= 0 && pos.y >= 0 &&
+                (pos.x as usize) < self.width && (pos.y as usize) < self.height &&
+                !self.cells[pos.y as usize][pos.x as usize]
+        })
+    }
+
+    pub fn drop(&self, piece: &TetrisPiece) -> TetrisPiece {
+        let mut dropped = *piece;
+        while self.fits(&dropped) {
+            dropped.move_by(Pos2D::xy(0, 1));
+        }
+        dropped.move_by(Pos2D::xy(0, -1));
+        dropped
+    }
+
+    // Locks the piece in and clears full rows, returning how many were cleared.
+    pub fn place(&mut self, piece: &TetrisPiece) -> u32 {
+        for pos in piece.iter() {
+            self.cells[pos.y as usize][pos.x as usize] = true;
+        }
+        let mut lines = 0;
+        let mut row = self.height - 2;
+        while row > 0 {
+            if (1..self.width-1).all(|x| self.cells[row][x]) {
+                self.cells.remove(row);
+                let mut empty_row = vec![false; self.width];
+                empty_row[0] = true;
+                empty_row[self.width - 1] = true;
+                self.cells.insert(1, empty_row);
+                lines += 1;
+            } else {
+                row -= 1;
+            }
+        }
+        lines
+    }
+
+    // Height of each column, measured from the floor.
+    fn column_heights(&self) -> Vec<i32> {
+        (1..self.width-1).map(|x| {
+            (1..self.height-1)
+                .find(|&y| self.cells[y][x])
+                .map_or(0, |y| (self.height - 1 - y) as i32)
+        }).collect()
+    }
+
+    fn holes(&self) -> i32 {
+        let mut holes = 0;
+        for x in 1..self.width-1 {
+            let mut is_covered = false;
+            for y in 1..self.height-1 {
+                if self.cells[y][x] {
+                    is_covered = true;
+                } else if is_covered {
+                    holes += 1;
+                }
+            }
+        }
+        holes
+    }
+
+    // Each empty cell with filled cells either side adds the depth of the
+    // well it is in so far.
+    fn wells(&self) -> i32 {
+        let mut wells = 0;
+        for x in 1..self.width-1 {
+            let mut depth = 0;
+            for y in 1..self.height-1 {
+                if !self.cells[y][x] && self.cells[y][x-1] && self.cells[y][x+1] {
+                    depth += 1;
+                    wells += depth;
+                } else {
+                    depth = 0;
+                }
+            }
+        }
+        wells
+    }
+
+    // Changes between filled and empty going along each row, walls included.
+    fn row_transitions(&self) -> i32 {
+        let mut transitions = 0;
+        for y in 1..self.height-1 {
+            for x in 1..self.width {
+                if self.cells[y][x] != self.cells[y][x-1] {
+                    transitions += 1;
+                }
+            }
+        }
+        transitions
+    }
+
+    // Changes between filled and empty going down each column, floor included.
+    fn column_transitions(&self) -> i32 {
+        let mut transitions = 0;
+        for x in 1..self.width-1 {
+            for y in 2..self.height {
+                if self.cells[y][x] != self.cells[y-1][x] {
+                    transitions += 1;
+                }
+            }
+        }
+        transitions
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub lines: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights::new()
+    }
+}
+
+impl Weights {
+    pub fn new() -> Self {
+        Weights {
+            aggregate_height: -0.510066,
+            lines: 0.760666,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+            wells: -0.1,
+            row_transitions: -0.1,
+            column_transitions: -0.15,
+        }
+    }
+
+    pub fn from_vec(values: &[f64]) -> Self {
+        Weights {
+            aggregate_height: values[0],
+            lines: values[1],
+            holes: values[2],
+            bumpiness: values[3],
+            wells: values[4],
+            row_transitions: values[5],
+            column_transitions: values[6],
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<f64> {
+        vec![self.aggregate_height, self.lines, self.holes, self.bumpiness,
+             self.wells, self.row_transitions, self.column_transitions]
+    }
+
+    // Weights saved as JSON, as written by the tuner.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut json = String::new();
+        File::open(path)?.read_to_string(&mut json)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        File::create(path)?.write_all(json.as_bytes())
+    }
+
+    fn evaluate(&self, field: &Field, lines: u32) -> f64 {
+        let heights = field.column_heights();
+        let aggregate_height: i32 = heights.iter().sum();
+        let bumpiness: i32 = heights.windows(2).map(|pair| (pair[0] - pair[1]).abs()).sum();
+        self.aggregate_height * aggregate_height as f64 +
+            self.lines * lines as f64 +
+            self.holes * field.holes() as f64 +
+            self.bumpiness * bumpiness as f64 +
+            self.wells * field.wells() as f64 +
+            self.row_transitions * field.row_transitions() as f64 +
+            self.column_transitions * field.column_transitions() as f64
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Move {
+    Left,
+    Right,
+    RotateRight,
+    RotateLeft,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+}
+
+static MOVES: [Move; 6] = [Move::Left, Move::Right, Move::RotateRight, Move::RotateLeft, Move::Rotate180, Move::SoftDrop];
+
+// Where the piece ends up, and the keys to get it there.
+#[derive(Clone)]
+pub struct Placement {
+    pub piece: TetrisPiece,
+    pub moves: Vec<Move>,
+    pub score: f64,
+}
+
+// Where the key moves the piece to, if it can go there.
+fn apply_move(field: &Field, piece: &TetrisPiece, key: Move) -> Option<TetrisPiece> {
+    let mut moved = *piece;
+    match key {
+        Move::Left => moved.move_by(Pos2D::xy(-1, 0)),
+        Move::Right => moved.move_by(Pos2D::xy(1, 0)),
+        Move::RotateRight => return piece.rotated_right(|rotated| field.fits(rotated)),
+        Move::RotateLeft => return piece.rotated_left(|rotated| field.fits(rotated)),
+        // Two turns right, kicks and all, as the board does it.
+        Move::Rotate180 => {
+            return piece.rotated_right(|rotated| field.fits(rotated))
+                .and_then(|half| half.rotated_right(|rotated| field.fits(rotated)));
+        }
+        Move::SoftDrop => moved.move_by(Pos2D::xy(0, 1)),
+        Move::HardDrop => {}
+    }
+    if field.fits(&moved) { Some(moved) } else { None }
+}
+
+// Where a piece is and which way it faces.
+type StateKey = (i32, i32, usize);
+
+fn state_key(piece: &TetrisPiece) -> StateKey {
+    (piece.pos.x, piece.pos.y, piece.orientation)
+}
+
+// Pieces that cover the same cells are the same placement, whatever their
+// orientation.
+pub fn cells_key(piece: &TetrisPiece) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = piece.iter().map(|pos| (pos.x, pos.y)).collect();
+    cells.sort();
+    cells
+}
+
+// Breadth first search over every position the piece can be moved to,
+// returning each distinct place it can be hard dropped from, with the
+// shortest key sequence that gets it there.
+pub fn find_placements(field: &Field, piece: &TetrisPiece) -> Vec<(TetrisPiece, Vec<Move>)> {
+    let mut placements = Vec::new();
+    if !field.fits(piece) {
+        return placements;
+    }
+
+    let mut came_from: HashMap<StateKey, (StateKey, Move)> = HashMap::new();
+    let mut seen_landings = HashSet::new();
+    let mut queue = VecDeque::new();
+    let start = state_key(piece);
+    came_from.insert(start, (start, Move::HardDrop));
+    queue.push_back(*piece);
+
+    while let Some(current) = queue.pop_front() {
+        let landing = field.drop(&current);
+        if seen_landings.insert(cells_key(&landing)) {
+            let mut moves = vec![Move::HardDrop];
+            let mut key = state_key(&current);
+            while key != start {
+                let (previous, key_move) = came_from[&key];
+                moves.push(key_move);
+                key = previous;
+            }
+            moves.reverse();
+            placements.push((landing, moves));
+        }
+
+        for &key_move in MOVES.iter() {
+            let next = match apply_move(field, &current, key_move) {
+                Some(next) => next,
+                None => continue,
+            };
+            if let Entry::Vacant(entry) = came_from.entry(state_key(&next)) {
+                entry.insert((state_key(&current), key_move));
+                queue.push_back(next);
+            }
+        }
+    }
+    placements
+}
+
+// The keys that move the piece from where it is now into the placement, if
+// it can still get there.
+pub fn path_to(field: &Field, piece: &TetrisPiece, target: &TetrisPiece) -> Option<Vec<Move>> {
+    let target_cells = cells_key(target);
+    find_placements(field, piece).into_iter()
+        .find(|(landing, _)| cells_key(landing) == target_cells)
+        .map(|(_, moves)| moves)
+}
+
+// How many placements are looked ahead from, the best by how they leave the
+// board, as looking ahead from every one takes too long past a piece or two.
+static BEAM_WIDTH: usize = 8;
+
+fn search(field: &Field, piece: &TetrisPiece, preview: &[TetrisPiece], weights: &Weights) -> Vec<Placement> {
+    let mut placements: Vec<(Placement, Field, u32)> = find_placements(field, piece).into_iter().map(|(landing, moves)| {
+        let mut next_field = field.clone();
+        let lines = next_field.place(&landing);
+        let placement = Placement {
+            piece: landing,
+            moves: moves,
+            score: weights.evaluate(&next_field, lines),
+        };
+        (placement, next_field, lines)
+    }).collect();
+    let (next_piece, rest) = match preview.split_first() {
+        Some(next) => next,
+        None => return placements.into_iter().map(|(placement, _, _)| placement).collect(),
+    };
+
+    placements.sort_by(|a, b| b.0.score.partial_cmp(&a.0.score).unwrap());
+    placements.truncate(BEAM_WIDTH);
+    placements.into_iter().map(|(mut placement, next_field, lines)| {
+        // Score by the best follow up, or give up on this one if the next
+        // piece can't go anywhere.
+        placement.score = weights.lines * lines as f64 + search(&next_field, next_piece, rest, weights).iter()
+            .map(|placement| placement.score)
+            .fold(-1e9, f64::max);
+        placement
+    }).collect()
+}
+
+// Scores the piece's placements, looking ahead through the preview from the
+// most promising, best first.
+pub fn rank_placements(field: &Field, piece: &TetrisPiece, preview: &[TetrisPiece], weights: &Weights) -> Vec<Placement> {
+    let mut ranked = search(field, piece, preview, weights);
+    ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    ranked
+}
+
+pub fn best_placement(field: &Field, piece: &TetrisPiece, preview: &[TetrisPiece], weights: &Weights) -> Option<Placement> {
+    rank_placements(field, piece, preview, weights).into_iter().next()
+}
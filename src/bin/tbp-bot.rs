@@ -0,0 +1,30 @@
+// Our AI as a Tetris Bot Protocol bot, for frontends that speak TBP. Takes
+// an optional weights file written by the tuner.
+
+extern crate rust_tetris;
+
+use rust_tetris::ai::{self, Weights};
+use rust_tetris::tbp;
+
+use std::env;
+
+static LOOKAHEAD: usize = 1;
+static SUGGESTIONS: usize = 5;
+
+fn main() {
+    let weights = match env::args().nth(1) {
+        Some(path) => Weights::load(&path).expect("Failed to load the weights"),
+        None => Weights::new(),
+    };
+    tbp::run_bot("rust-tetris", |state| {
+        let piece = match state.current_piece() {
+            Some(piece) => piece,
+            None => return Vec::new(),
+        };
+        let preview = state.preview(LOOKAHEAD);
+        ai::rank_placements(&state.field, &piece, &preview, &weights).iter()
+            .take(SUGGESTIONS)
+            .filter_map(|placement| tbp::placement_move(&state.field, placement))
+            .collect()
+    }).unwrap();
+}
@@ -0,0 +1,22 @@
+// A TBP bot that always drops the piece straight down from where it spawns,
+// for checking a frontend without a real bot's opinions getting in the way.
+
+extern crate rust_tetris;
+
+use rust_tetris::ai::{Move, Placement};
+use rust_tetris::tbp;
+
+fn main() {
+    tbp::run_bot("mock", |state| {
+        let piece = match state.current_piece() {
+            Some(piece) => piece,
+            None => return Vec::new(),
+        };
+        let placement = Placement {
+            piece: state.field.drop(&piece),
+            moves: vec![Move::HardDrop],
+            score: 0.0,
+        };
+        tbp::placement_move(&state.field, &placement).into_iter().collect()
+    }).unwrap();
+}
@@ -0,0 +1,166 @@
+// Plays batches of seeded games without a window, as fast as possible, and
+// prints statistics for each game as JSON or CSV.
+
+extern crate rust_tetris;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use rust_tetris::Controller;
+use rust_tetris::cpu::Cpu;
+use rust_tetris::replay::{Replay, ReplayPlayer};
+use rust_tetris::sim::{self, GameStats, Ruleset, RULESETS};
+use rust_tetris::tbp::ExternalBot;
+
+use std::env;
+use std::fs;
+use std::process;
+
+struct Options {
+    games: usize,
+    seed: usize,
+    ruleset: String,
+    bot: String,
+    tbp_command: Option<Vec<String>>,
+    replay: Option<String>,
+    max_frames: u32,
+    format: String,
+    save_replays: Option<String>,
+}
+
+impl Options {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            games: 10,
+            seed: 0,
+            ruleset: "marathon".to_string(),
+            bot: "medium".to_string(),
+            tbp_command: None,
+            replay: None,
+            // Five minutes.
+            max_frames: 18000,
+            format: "json".to_string(),
+            save_replays: None,
+        };
+        for pair in args[1..].chunks(2) {
+            let value = match pair.get(1) {
+                Some(value) => value.clone(),
+                None => return Err(format!("{} needs a value", pair[0])),
+            };
+            let number = || value.parse().map_err(|_| format!("{} needs a number", pair[0]));
+            match pair[0].as_str() {
+                "--games" => options.games = number()?,
+                "--seed" => options.seed = number()?,
+                "--ruleset" => options.ruleset = value,
+                "--bot" => options.bot = value,
+                "--tbp" => options.tbp_command = Some(value.split_whitespace().map(|s| s.to_string()).collect()),
+                "--replay" => options.replay = Some(value),
+                "--max-frames" => options.max_frames = number()? as u32,
+                "--format" => options.format = value,
+                "--save-replays" => options.save_replays = Some(value),
+                arg => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+        if !RULESETS.contains(&options.ruleset.as_str()) {
+            return Err(format!("Unknown ruleset {}, expected one of {}", options.ruleset, RULESETS.join(", ")));
+        }
+        if options.format != "json" && options.format != "csv" {
+            return Err(format!("Unknown format {}, expected json or csv", options.format));
+        }
+        Ok(options)
+    }
+
+    // The CPU's mistakes are seeded along with the game.
+    fn controller(&self, seed: usize) -> Box<dyn Controller> {
+        match self.tbp_command {
+            Some(ref command) => Box::new(ExternalBot::spawn(command).expect("Failed to start the bot")),
+            None => Box::new(Cpu::with_seed(&self.bot, seed)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Summary {
+    games: usize,
+    mean_lines: f64,
+    mean_score: f64,
+    mean_pieces: f64,
+    mean_frames: f64,
+    completed: usize,
+    topped_out: usize,
+}
+
+impl Summary {
+    fn new(stats: &[GameStats]) -> Self {
+        let mean = |f: &dyn Fn(&GameStats) -> u32| {
+            stats.iter().map(|game| f(game) as f64).sum::<f64>() / stats.len().max(1) as f64
+        };
+        Summary {
+            games: stats.len(),
+            mean_lines: mean(&|game| game.lines),
+            mean_score: mean(&|game| game.score),
+            mean_pieces: mean(&|game| game.pieces),
+            mean_frames: mean(&|game| game.frames),
+            completed: stats.iter().filter(|game| game.is_complete).count(),
+            topped_out: stats.iter().filter(|game| game.top_out.is_some()).count(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    games: &'a [GameStats],
+    summary: Summary,
+}
+
+fn print_csv(stats: &[GameStats]) {
+    println!("seed,lines,score,pieces,frames,is_complete,top_out");
+    for game in stats {
+        println!("{},{},{},{},{},{},{}", game.seed, game.lines, game.score, game.pieces, game.frames,
+                 game.is_complete, game.top_out.as_ref().map_or("", |s| s.as_str()));
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = Options::from_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    if let Some(ref dir) = options.save_replays {
+        fs::create_dir_all(dir).unwrap_or_else(|e| {
+            eprintln!("Failed to create {}: {}", dir, e);
+            process::exit(1);
+        });
+    }
+
+    let mut stats = Vec::new();
+    if let Some(ref path) = options.replay {
+        // A replay brings its own ruleset and seed, and ends when its inputs do.
+        let recorded = Replay::load(path).expect("Failed to load the replay");
+        let game = Ruleset::new(&recorded.ruleset, recorded.seed).expect("Unknown ruleset in the replay");
+        let mut replay = Replay::new(&recorded.ruleset, recorded.seed);
+        let frames = recorded.inputs.len() as u32;
+        stats.push(sim::run(game, &mut ReplayPlayer::new(&recorded), frames, &mut replay));
+    } else {
+        for seed in options.seed..options.seed + options.games {
+            let game = Ruleset::new(&options.ruleset, seed).unwrap();
+            let mut replay = Replay::new(&options.ruleset, seed);
+            stats.push(sim::run(game, &mut *options.controller(seed), options.max_frames, &mut replay));
+            if let Some(ref dir) = options.save_replays {
+                replay.save(&format!("{}/{}-{}.json", dir, options.ruleset, seed)).expect("Failed to save the replay");
+            }
+        }
+    }
+
+    if options.format == "csv" {
+        print_csv(&stats);
+    } else {
+        let report = Report {
+            summary: Summary::new(&stats),
+            games: &stats,
+        };
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+}
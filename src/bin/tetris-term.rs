@@ -0,0 +1,560 @@
+// The game in a terminal, for machines without a display: the frame is drawn
+// with ANSI colors, two characters to a block so they come out square, and
+// keys are read in raw mode. Takes the same arguments as the SDL game.
+
+extern crate rust_tetris;
+
+use rust_tetris::{Color, Input};
+use rust_tetris::controls::{self, Action, ActionKeys, Controls, ACTIONS};
+use rust_tetris::effects::{EffectSettings, Effects};
+use rust_tetris::finesse::FinesseTracker;
+use rust_tetris::frame::{Block, Frame, TextSize, Tile, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rust_tetris::fumen::{self, Page};
+use rust_tetris::game::{self, Game};
+use rust_tetris::theme::{self, Glyph, Glyphs, Pattern, Theme};
+
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Terminals have no key up, only the key again while it is held. A key
+// pressed again within REPEAT_DELAY, the wait before the terminal starts
+// repeating it, counts as held down; once it is repeating, it is let go when
+// it has not come for REPEAT_GAP. Taps closer together than REPEAT_DELAY count
+// as holding the key.
+static REPEAT_DELAY: u64 = 500;
+static REPEAT_GAP: u64 = 100;
+
+// Raw mode on the terminal, for as long as this is around.
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn new() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        // The alternate screen, without a cursor.
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(RawTerminal {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(File::open("/dev/tty")?))
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Tab,
+    Esc,
+    Char(char),
+}
+
+// The key SDL calls `name`, if a terminal can send it. Letters come as lower
+// case, whatever the case of the name.
+fn key_from_name(name: &str) -> Option<Key> {
+    match name.to_lowercase().as_str() {
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "return" | "enter" => Some(Key::Enter),
+        "tab" => Some(Key::Tab),
+        "space" => Some(Key::Char(' ')),
+        name if name.chars().count() == 1 => name.chars().next().filter(|c| c.is_ascii_graphic()).map(Key::Char),
+        _ => None,
+    }
+}
+
+// Reading blocks, so it happens on another thread.
+fn read_stdin() -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        loop {
+            match io::stdin().read(&mut buf) {
+                Ok(count) if count > 0 => {
+                    if sender.send(buf[..count].to_vec()).is_err() {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+    });
+    receiver
+}
+
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let key = match bytes[i] {
+            // Arrow keys are ESC [ A to D, or ESC O A to D.
+            0x1b if i + 2 < bytes.len() && (bytes[i + 1] == b'[' || bytes[i + 1] == b'O') => {
+                i += 2;
+                match bytes[i] {
+                    b'A' => Some(Key::Up),
+                    b'B' => Some(Key::Down),
+                    b'C' => Some(Key::Right),
+                    b'D' => Some(Key::Left),
+                    _ => None,
+                }
+            }
+            // Ctrl-C quits as well, since raw mode doesn't turn it into a signal.
+            0x1b | 3 => Some(Key::Esc),
+            b'\r' | b'\n' => Some(Key::Enter),
+            b'\t' => Some(Key::Tab),
+            byte if (b' '..0x7f).contains(&byte) => Some(Key::Char((byte as char).to_ascii_lowercase())),
+            _ => None,
+        };
+        keys.extend(key);
+        i += 1;
+    }
+    keys
+}
+
+struct HeldKey {
+    last_pressed: Instant,
+    is_repeating: bool,
+}
+
+// Works out which keys are held from when they last came in.
+struct KeyState {
+    keys: HashMap<Key, HeldKey>,
+}
+
+impl KeyState {
+    fn new() -> Self {
+        KeyState {
+            keys: HashMap::new(),
+        }
+    }
+
+    // Returns whether the key was already held.
+    fn press(&mut self, key: Key, now: Instant) -> bool {
+        let repeat = self.is_held(key, now);
+        self.keys.insert(key, HeldKey { last_pressed: now, is_repeating: repeat });
+        repeat
+    }
+
+    fn is_held(&self, key: Key, now: Instant) -> bool {
+        self.keys.get(&key).is_some_and(|held| {
+            let wait = if held.is_repeating { REPEAT_GAP } else { REPEAT_DELAY };
+            now.duration_since(held.last_pressed) < Duration::from_millis(wait)
+        })
+    }
+
+    // Only while the key keeps coming, so a tap doesn't soft drop for long.
+    fn is_down(&self, key: Key, now: Instant) -> bool {
+        self.keys.get(&key).is_some_and(|held| {
+            now.duration_since(held.last_pressed) < Duration::from_millis(REPEAT_GAP)
+        })
+    }
+}
+
+// A player's keys, leaving out the ones a terminal can't send, like Shift.
+struct KeyBindings {
+    keys: Vec<(Key, Action)>,
+}
+
+impl KeyBindings {
+    fn new(action_keys: &ActionKeys) -> Self {
+        let mut keys = Vec::new();
+        for &action in ACTIONS.iter() {
+            keys.extend(action_keys.keys(action).iter().filter_map(|name| key_from_name(name)).map(|key| (key, action)));
+        }
+        KeyBindings {
+            keys: keys,
+        }
+    }
+
+    fn actions(&self, key: Key) -> Vec<Action> {
+        self.keys.iter().filter(|&&(bound, _)| bound == key).map(|&(_, action)| action).collect()
+    }
+
+    fn key_down(&self, input: &mut Input, key: Key, repeat: bool) {
+        controls::press(input, &self.actions(key), repeat);
+    }
+
+    fn is_soft_dropping(&self, key_state: &KeyState, now: Instant) -> bool {
+        self.keys.iter().any(|&(key, action)| action == Action::SoftDrop && key_state.is_down(key, now))
+    }
+}
+
+// One set of keys per player.
+fn key_bindings(game: &Game, controls: &Controls) -> Vec<KeyBindings> {
+    controls.players(game).into_iter().map(KeyBindings::new).collect()
+}
+
+// What the keys do, for the help text. Pausing and restarting are left out
+// in versus, for room between the boards.
+fn help(game: &Game, controls: &Controls) -> Vec<Vec<String>> {
+    let players = controls.players(game);
+    let is_known = |name: &str| key_from_name(name).is_some();
+    let mut help: Vec<Vec<String>> = players.iter().map(|keys| keys.help(&is_known, players.len() == 1)).collect();
+    if players.len() == 1 && game.board().is_some() {
+        help[0].extend(vec!["F for a fumen".to_string(), "E for effects".to_string()]);
+    }
+    help.last_mut().unwrap().push("Esc to quit".to_string());
+    help
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct Cell {
+    ch: char,
+    fg: Option<Color>,
+    bg: Option<Color>,
+    is_bold: bool,
+}
+
+static BLANK: Cell = Cell { ch: ' ', fg: None, bg: None, is_bold: false };
+
+// The screen as characters, each block two characters wide.
+struct Screen {
+    cells: Vec<Vec<Cell>>,
+}
+
+impl Screen {
+    fn new() -> Self {
+        Screen {
+            cells: vec![vec![BLANK; SCREEN_WIDTH as usize * 2]; SCREEN_HEIGHT as usize],
+        }
+    }
+
+    fn cell(&mut self, x: i32, y: i32) -> Option<&mut Cell> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.cells.get_mut(y as usize).and_then(|row| row.get_mut(x as usize))
+    }
+
+    fn fill(&mut self, x: i32, y: i32, color: Color) {
+        for dx in 0..2 {
+            if let Some(cell) = self.cell(x * 2 + dx, y) {
+                cell.bg = Some(color);
+            }
+        }
+    }
+
+    // Marks a piece's block, so it can be told apart without its color.
+    fn glyph(&mut self, x: i32, y: i32, glyph: Glyph, color: Color) {
+        let chars = match glyph {
+            Glyph::Letter(letter) => [letter, letter],
+            Glyph::Pattern(Pattern::HorizontalLine) => ['=', '='],
+            Glyph::Pattern(Pattern::VerticalLine) => ['|', '|'],
+            Glyph::Pattern(Pattern::Square) => ['[', ']'],
+            Glyph::Pattern(Pattern::Dot) => ['(', ')'],
+            Glyph::Pattern(Pattern::Rising) => ['/', '/'],
+            Glyph::Pattern(Pattern::Falling) => ['\\', '\\'],
+            Glyph::Pattern(Pattern::Cross) => ['+', '+'],
+        };
+        for (dx, &ch) in chars.iter().enumerate() {
+            if let Some(cell) = self.cell(x * 2 + dx as i32, y) {
+                cell.ch = ch;
+                cell.fg = Some(theme::glyph_color(color));
+            }
+        }
+    }
+
+    // Text goes on top of whatever it is over.
+    fn text(&mut self, x: i32, y: i32, text: &str, color: Color, is_bold: bool) {
+        for (i, ch) in text.chars().enumerate() {
+            if let Some(cell) = self.cell(x * 2 + i as i32, y) {
+                cell.ch = ch;
+                cell.fg = Some(color);
+                cell.is_bold = is_bold;
+            }
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.cells.iter().map(|row| {
+            let mut line = String::new();
+            let mut last = BLANK;
+            for cell in row.iter() {
+                if (cell.fg, cell.bg, cell.is_bold) != (last.fg, last.bg, last.is_bold) {
+                    line += "\x1b[0m";
+                    if cell.is_bold {
+                        line += "\x1b[1m";
+                    }
+                    if let Some(fg) = cell.fg {
+                        line += &format!("\x1b[38;2;{};{};{}m", fg.r, fg.g, fg.b);
+                    }
+                    if let Some(bg) = cell.bg {
+                        line += &format!("\x1b[48;2;{};{};{}m", bg.r, bg.g, bg.b);
+                    }
+                    last = *cell;
+                }
+                line.push(cell.ch);
+            }
+            line + "\x1b[0m"
+        }).collect()
+    }
+}
+
+// Themes only bring their colors and glyphs here, there are no images.
+fn draw_block(screen: &mut Screen, theme: &Theme, x: i32, y: i32, block: &Block) {
+    let color = theme.color(block);
+    // Ghosts are dim.
+    if block.is_ghost {
+        screen.fill(x, y, Color::RGB(color.r / 3, color.g / 3, color.b / 3));
+        return;
+    }
+    screen.fill(x, y, color);
+    if let Tile::Piece(kind) = block.tile {
+        if let Some(glyph) = theme.glyph(kind) {
+            screen.glyph(x, y, glyph, color);
+        }
+    }
+}
+
+fn draw_frame(frame: &Frame, theme: &Theme) -> Vec<String> {
+    let mut screen = Screen::new();
+    for board in frame.boards.iter() {
+        for (y, row) in board.blocks.iter().enumerate() {
+            for (x, block) in row.iter().enumerate() {
+                if let Some(ref block) = *block {
+                    draw_block(&mut screen, theme, board.pos.x + x as i32, board.pos.y + y as i32, block);
+                }
+            }
+        }
+        // Incoming garbage, stacked up from the bottom of the board, in the
+        // character left of it.
+        if let Some(incoming) = board.incoming {
+            let bottom = board.pos.y + board.height() - 2;
+            for y in 0..cmp::min(incoming as i32, board.height() - 2) {
+                if let Some(cell) = screen.cell(board.pos.x * 2 - 1, bottom - y) {
+                    cell.bg = Some(Color::RGB(255, 0, 0));
+                }
+            }
+        }
+    }
+
+    for piece in frame.pieces.iter() {
+        for cell in piece.cells.iter() {
+            draw_block(&mut screen, theme, piece.pos.x + cell.x, piece.pos.y + cell.y, &piece.block);
+        }
+    }
+
+    // Particles are a character each. The screen can't shake by less than
+    // a character, so it doesn't.
+    for particle in frame.particles.iter() {
+        if let Some(cell) = screen.cell((particle.x * 2.0) as i32, particle.y as i32) {
+            cell.ch = '*';
+            cell.fg = Some(particle.color);
+        }
+    }
+
+    for panel in frame.panels.iter() {
+        let mut y = panel.pos.y;
+        for line in panel.lines.iter() {
+            screen.text(panel.pos.x, y, &line.text, line.color, line.size == TextSize::Large);
+            y += line.size.rows();
+        }
+    }
+    screen.lines()
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    game::load_pieces(&mut args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let effect_settings = game::take_option(&mut args, "--effects")
+        .map_or(Ok(EffectSettings::all()), |names| EffectSettings::from_names(&names))
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+    let controls_path = game::take_option(&mut args, "--controls").unwrap_or("controls.toml".to_string());
+    let controls = Controls::load(&controls_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    // `--theme` and `--glyphs` beat the ones picked in the game's menu.
+    let theme_name = game::take_option(&mut args, "--theme").unwrap_or(controls.theme.clone());
+    let mut theme = Theme::named(&theme_name).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    if let Some(name) = game::take_option(&mut args, "--glyphs") {
+        theme.glyphs = Glyphs::from_name(&name).unwrap_or_else(|| {
+            eprintln!("--glyphs is none, letters or patterns");
+            process::exit(1);
+        });
+    } else if let Some(glyphs) = controls.glyphs {
+        theme.glyphs = glyphs;
+    }
+    let mut game = Game::from_args(&args);
+
+    let terminal = RawTerminal::new().unwrap_or_else(|e| {
+        eprintln!("Failed to put the terminal in raw mode: {}", e);
+        process::exit(1);
+    });
+    let keys_read = read_stdin();
+    let mut key_state = KeyState::new();
+
+    let mut finesse = FinesseTracker::new();
+    let mut effects = Effects::new(effect_settings);
+    let mut fumen_text: Option<String> = None;
+    let mut last_frame = String::new();
+
+    let key_bindings = key_bindings(&game, &controls);
+    let help = help(&game, &controls);
+    let mut inputs: Vec<Input> = key_bindings.iter().map(|_| Input::new()).collect();
+    let mut is_paused = false;
+    let mut last_updated = Instant::now();
+
+    'running: loop {
+        let now = Instant::now();
+        while let Ok(bytes) = keys_read.try_recv() {
+            for key in parse_keys(&bytes) {
+                let repeat = key_state.press(key, now);
+                let actions: Vec<Action> = key_bindings.iter().flat_map(|keys| keys.actions(key)).collect();
+                match key {
+                    Key::Esc => break 'running,
+                    _ if !repeat && actions.contains(&Action::Pause) => {
+                        is_paused = !is_paused;
+                    }
+                    _ if !repeat && actions.contains(&Action::Restart) => {
+                        game.restart();
+                        is_paused = false;
+                    }
+                    Key::Char('h') if !repeat => {
+                        if let Game::Pc(ref mut pc_game) = game {
+                            pc_game.show_hints = !pc_game.show_hints;
+                        }
+                    }
+                    Key::Char('e') if !repeat => {
+                        effects.show = !effects.show;
+                    }
+                    // Share the board as a fumen.
+                    Key::Char('f') if !repeat && game.board().is_some() => {
+                        fumen_text = Some(Page::from_board(game.board().unwrap(), None)
+                            .map_or_else(|e| e, |page| fumen::encode(&[page])));
+                    }
+                    _ if is_paused => {}
+                    _ => {
+                        for (input, keys) in inputs.iter_mut().zip(key_bindings.iter()) {
+                            keys.key_down(input, key, repeat);
+                        }
+                    }
+                }
+            }
+        }
+
+        if now.duration_since(last_updated) <= Duration::new(0, 1_000_000_000 / 60) {
+            thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+        for (input, keys) in inputs.iter_mut().zip(key_bindings.iter()) {
+            input.down_key_pressed = keys.is_soft_dropping(&key_state, now);
+        }
+        if !is_paused {
+            game.update(&inputs);
+            if let Some(board) = game.board() {
+                finesse.update(board, &inputs[0]);
+            }
+            effects.update(&game);
+        }
+        for input in inputs.iter_mut() {
+            input.reset();
+        }
+        last_updated = now;
+
+        let mut frame = Frame::new(&game, &finesse, &controls, &help);
+        effects.apply(&mut frame);
+        if is_paused {
+            let keys = controls.players(&game).iter().flat_map(|keys| keys.pause.first()).next().cloned();
+            frame.pause(&format!("Press {} to resume", keys.unwrap_or_default()));
+        }
+        let mut lines = draw_frame(&frame, &theme);
+        if let Some(ref data) = fumen_text {
+            lines.push(String::new());
+            lines.push(data.clone());
+        }
+        // Raw mode needs the carriage returns. Frames are only sent when they
+        // change, which matters over SSH.
+        let frame = format!("\x1b[H{}\x1b[K\x1b[J", lines.join("\x1b[K\r\n"));
+        if frame != last_frame {
+            print!("{}", frame);
+            io::stdout().flush().unwrap();
+            last_frame = frame;
+        }
+    }
+    drop(terminal);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{parse_keys, Key, KeyState, REPEAT_DELAY, REPEAT_GAP};
+
+    fn after(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    // A key coming again before the terminal would repeat it is held, and
+    // stays held while the repeats keep coming.
+    #[test]
+    fn keys_repeat_until_they_stop_coming() {
+        let start = Instant::now();
+        let mut key_state = KeyState::new();
+        assert!(!key_state.press(Key::Left, start));
+        assert!(key_state.press(Key::Left, after(start, REPEAT_DELAY - 10)));
+        assert!(key_state.press(Key::Left, after(start, REPEAT_DELAY + REPEAT_GAP - 20)));
+        // The repeats stopped, so this is a fresh press.
+        assert!(!key_state.press(Key::Left, after(start, REPEAT_DELAY + 3 * REPEAT_GAP)));
+        // Other keys are held or not on their own.
+        assert!(!key_state.press(Key::Right, after(start, REPEAT_DELAY + 3 * REPEAT_GAP)));
+    }
+
+    #[test]
+    fn keys_are_down_while_they_keep_coming() {
+        let start = Instant::now();
+        let mut key_state = KeyState::new();
+        key_state.press(Key::Down, start);
+        assert!(key_state.is_down(Key::Down, after(start, REPEAT_GAP - 10)));
+        assert!(!key_state.is_down(Key::Down, after(start, REPEAT_GAP + 10)));
+        assert!(!key_state.is_down(Key::Up, start));
+    }
+
+    #[test]
+    fn parses_arrows_and_letters() {
+        let keys = parse_keys(b"\x1b[A\x1bOBxZ \x03");
+        assert!(keys == vec![Key::Up, Key::Down, Key::Char('x'), Key::Char('z'), Key::Char(' '), Key::Esc]);
+    }
+}
@@ -0,0 +1,195 @@
+// Evolves the AI's evaluation weights with a genetic algorithm, playing
+// seeded games without a window across several threads.
+//
+// Each generation every candidate plays the same games, scoring the lines it
+// clears. The worst are then replaced by children of tournament winners,
+// crossed over in proportion to their fitness and sometimes mutated.
+
+extern crate rand;
+extern crate rust_tetris;
+
+use rand::{thread_rng, Rng};
+
+use rust_tetris::{start_pos, tetris_board_height, tetris_board_width, RandomTetrisPieceGenerator};
+use rust_tetris::ai::{self, Field, Weights};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+
+// Fraction of the population replaced each generation.
+static OFFSPRING_RATE: f64 = 0.3;
+// Fraction of the population drawn for each tournament.
+static TOURNAMENT_RATE: f64 = 0.1;
+static MUTATION_RATE: f64 = 0.05;
+static MUTATION_STEP: f64 = 0.2;
+
+struct Options {
+    generations: usize,
+    population: usize,
+    games: usize,
+    max_pieces: u32,
+    threads: usize,
+    out_dir: String,
+}
+
+impl Options {
+    fn from_args(args: &[String]) -> Self {
+        let mut options = Options {
+            generations: 50,
+            population: 100,
+            games: 20,
+            max_pieces: 500,
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            out_dir: "tuning".to_string(),
+        };
+        for pair in args[1..].chunks(2) {
+            let value = pair.get(1).map_or("", |s| s.as_str());
+            let number = || value.parse().unwrap_or_else(|_| panic!("{} needs a number", pair[0]));
+            match pair[0].as_str() {
+                "--generations" => options.generations = number(),
+                "--population" => options.population = number(),
+                "--games" => options.games = number(),
+                "--pieces" => options.max_pieces = number() as u32,
+                "--threads" => options.threads = number(),
+                "--out" => options.out_dir = value.to_string(),
+                arg => panic!("Unknown option {}", arg),
+            }
+        }
+        // Tournaments pick the best two of a sample.
+        if options.population < 2 {
+            panic!("--population needs at least 2");
+        }
+        let counts = [
+            ("--generations", options.generations),
+            ("--games", options.games),
+            ("--pieces", options.max_pieces as usize),
+            ("--threads", options.threads),
+        ];
+        for &(name, count) in counts.iter() {
+            if count == 0 {
+                panic!("{} needs at least 1", name);
+            }
+        }
+        options
+    }
+}
+
+// Only the direction of the weights matters, so keep them at unit length.
+fn normalize(values: &mut [f64]) {
+    let length = values.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if length > 0.0 {
+        for v in values.iter_mut() {
+            *v /= length;
+        }
+    }
+}
+
+fn random_weights() -> Weights {
+    let mut values: Vec<f64> = Weights::new().to_vec().iter()
+        .map(|_| thread_rng().gen_range(-1.0, 1.0))
+        .collect();
+    normalize(&mut values);
+    Weights::from_vec(&values)
+}
+
+// Lines cleared placing pieces until topping out or running out of pieces.
+fn play(weights: &Weights, seed: usize, max_pieces: u32) -> u32 {
+    let mut field = Field::empty(tetris_board_width, tetris_board_height);
+    let mut tetris_gen = RandomTetrisPieceGenerator::with_seed(seed);
+    let mut lines = 0;
+    for _ in 0..max_pieces {
+        let piece = tetris_gen.get_next_piece(start_pos);
+        match ai::best_placement(&field, &piece, &[], weights) {
+            Some(placement) => lines += field.place(&placement.piece),
+            None => break,
+        }
+    }
+    lines
+}
+
+// Plays every candidate through the games starting at `first_seed`, sharing
+// the candidates out between the threads.
+fn evaluate(population: &[Weights], options: &Options, first_seed: usize) -> Vec<f64> {
+    let population = Arc::new(population.to_vec());
+    let handles: Vec<_> = (0..options.threads).map(|t| {
+        let population = population.clone();
+        let (threads, games, max_pieces) = (options.threads, options.games, options.max_pieces);
+        thread::spawn(move || {
+            (t..population.len()).step_by(threads).map(|i| {
+                let lines: u32 = (0..games).map(|game| play(&population[i], first_seed + game, max_pieces)).sum();
+                (i, lines as f64)
+            }).collect::<Vec<_>>()
+        })
+    }).collect();
+
+    let mut fitness = vec![0.0; population.len()];
+    for handle in handles {
+        for (i, lines) in handle.join().unwrap() {
+            fitness[i] = lines;
+        }
+    }
+    fitness
+}
+
+// The best two of a random sample, best first.
+fn tournament(fitness: &[f64]) -> (usize, usize) {
+    let size = ((fitness.len() as f64 * TOURNAMENT_RATE) as usize).max(2);
+    let mut sample = rand::seq::sample_indices(&mut thread_rng(), fitness.len(), size);
+    sample.sort_by(|&a, &b| fitness[b].partial_cmp(&fitness[a]).unwrap());
+    (sample[0], sample[1])
+}
+
+fn crossover(a: &Weights, a_fitness: f64, b: &Weights, b_fitness: f64) -> Weights {
+    let total = a_fitness + b_fitness;
+    let share = if total > 0.0 { a_fitness / total } else { 0.5 };
+    let mut values: Vec<f64> = a.to_vec().iter().zip(b.to_vec().iter())
+        .map(|(a, b)| a * share + b * (1.0 - share))
+        .collect();
+    if thread_rng().gen::<f64>() < MUTATION_RATE {
+        let i = thread_rng().gen_range(0, values.len());
+        values[i] += thread_rng().gen_range(-MUTATION_STEP, MUTATION_STEP);
+    }
+    normalize(&mut values);
+    Weights::from_vec(&values)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = Options::from_args(&args);
+    fs::create_dir_all(&options.out_dir).expect("Failed to create the output directory");
+    let weights_path = format!("{}/best_weights.json", options.out_dir);
+    let mut curve = File::create(format!("{}/learning_curve.csv", options.out_dir))
+        .expect("Failed to create the learning curve");
+    writeln!(curve, "generation,best,mean,worst").unwrap();
+
+    let mut population: Vec<Weights> = (0..options.population).map(|_| random_weights()).collect();
+    for generation in 0..options.generations {
+        // New games each generation, so nobody gets tuned to one piece sequence.
+        let fitness = evaluate(&population, &options, generation * options.games);
+
+        let mut ranked: Vec<usize> = (0..population.len()).collect();
+        ranked.sort_by(|&a, &b| fitness[b].partial_cmp(&fitness[a]).unwrap());
+        let games = options.games as f64;
+        let best = fitness[ranked[0]] / games;
+        let mean = fitness.iter().sum::<f64>() / fitness.len() as f64 / games;
+        let worst = fitness[ranked[ranked.len() - 1]] / games;
+
+        println!("Generation {}: best {:.1}, mean {:.1}, worst {:.1} lines per game", generation, best, mean, worst);
+        writeln!(curve, "{},{},{},{}", generation, best, mean, worst).unwrap();
+        curve.flush().unwrap();
+        population[ranked[0]].save(&weights_path).expect("Failed to save the weights");
+
+        let offspring_count = (population.len() as f64 * OFFSPRING_RATE) as usize;
+        let offspring: Vec<Weights> = (0..offspring_count).map(|_| {
+            let (a, b) = tournament(&fitness);
+            crossover(&population[a], fitness[a], &population[b], fitness[b])
+        }).collect();
+        for (&i, child) in ranked.iter().rev().zip(offspring) {
+            population[i] = child;
+        }
+    }
+    println!("Best weights written to {}", weights_path);
+}
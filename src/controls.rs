@@ -0,0 +1,421 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use toml;
+
+use game::Game;
+use theme::{self, Glyphs};
+use super::Input;
+
+// What keys can do. A press of each goes to the player whose key it is,
+// except Pause and Restart, which are for the whole game.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateRight,
+    RotateLeft,
+    Rotate180,
+    Hold,
+    Pause,
+    Restart,
+}
+
+pub static ACTIONS: [Action; 10] = [Action::MoveLeft, Action::MoveRight, Action::SoftDrop, Action::HardDrop,
+                                    Action::RotateRight, Action::RotateLeft, Action::Rotate180, Action::Hold,
+                                    Action::Pause, Action::Restart];
+
+impl Action {
+    pub fn name(&self) -> &str {
+        match *self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::RotateRight => "Rotate right",
+            Action::RotateLeft => "Rotate left",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::Restart => "Restart",
+        }
+    }
+
+    // For the help text, after the keys: "Z to rotate left".
+    pub fn description(&self) -> &str {
+        match *self {
+            Action::MoveLeft => "move left",
+            Action::MoveRight => "move right",
+            Action::SoftDrop => "drop",
+            Action::HardDrop => "hard drop",
+            Action::RotateRight => "rotate",
+            Action::RotateLeft => "rotate left",
+            Action::Rotate180 => "rotate 180",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+        }
+    }
+}
+
+// Presses a player's keys for the actions on their input. `repeat` is for
+// keys only being held down. Soft drop is polled instead, while the key is
+// down, and pause and restart are left to the front end.
+pub fn press(input: &mut Input, actions: &[Action], repeat: bool) {
+    for &action in actions {
+        let (pressed, is_repeat) = match action {
+            Action::MoveLeft => (&mut input.left_key_pressed, &mut input.left_is_repeat),
+            Action::MoveRight => (&mut input.right_key_pressed, &mut input.right_is_repeat),
+            Action::RotateRight => (&mut input.up_key_pressed, &mut input.up_is_repeat),
+            Action::RotateLeft => (&mut input.rotate_left_key_pressed, &mut input.rotate_left_is_repeat),
+            Action::Rotate180 => (&mut input.rotate_180_key_pressed, &mut input.rotate_180_is_repeat),
+            Action::HardDrop => {
+                input.hard_drop_key_pressed = true;
+                continue;
+            }
+            Action::Hold => {
+                input.hold_key_pressed = true;
+                continue;
+            }
+            Action::SoftDrop | Action::Pause | Action::Restart => continue,
+        };
+        // A fresh press of the key this frame beats it being held.
+        *is_repeat = repeat && (*is_repeat || !*pressed);
+        *pressed = true;
+    }
+}
+
+// The keys for each action, by SDL's names for them, like "Left", "Space",
+// "Z" or "Right Shift". An action can have any number of keys, or none.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionKeys {
+    pub move_left: Vec<String>,
+    pub move_right: Vec<String>,
+    pub soft_drop: Vec<String>,
+    pub hard_drop: Vec<String>,
+    pub rotate_right: Vec<String>,
+    pub rotate_left: Vec<String>,
+    pub rotate_180: Vec<String>,
+    pub hold: Vec<String>,
+    pub pause: Vec<String>,
+    pub restart: Vec<String>,
+}
+
+// Keys the front ends keep for their own jobs: quitting, the controls menu,
+// fullscreen, effects, fumens, hints and snapshots. They can't be bound, as
+// their jobs would take their presses first.
+pub static RESERVED_KEYS: [&str; 7] = ["Escape", "F1", "F11", "E", "F", "H", "P"];
+
+fn check_key(name: &str) -> Result<(), String> {
+    if RESERVED_KEYS.iter().any(|key| key.eq_ignore_ascii_case(name)) {
+        Err(format!("{} can't be bound, as the game keeps it for itself", name))
+    } else {
+        Ok(())
+    }
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+impl ActionKeys {
+    pub fn keys(&self, action: Action) -> &Vec<String> {
+        match action {
+            Action::MoveLeft => &self.move_left,
+            Action::MoveRight => &self.move_right,
+            Action::SoftDrop => &self.soft_drop,
+            Action::HardDrop => &self.hard_drop,
+            Action::RotateRight => &self.rotate_right,
+            Action::RotateLeft => &self.rotate_left,
+            Action::Rotate180 => &self.rotate_180,
+            Action::Hold => &self.hold,
+            Action::Pause => &self.pause,
+            Action::Restart => &self.restart,
+        }
+    }
+
+    pub fn keys_mut(&mut self, action: Action) -> &mut Vec<String> {
+        match action {
+            Action::MoveLeft => &mut self.move_left,
+            Action::MoveRight => &mut self.move_right,
+            Action::SoftDrop => &mut self.soft_drop,
+            Action::HardDrop => &mut self.hard_drop,
+            Action::RotateRight => &mut self.rotate_right,
+            Action::RotateLeft => &mut self.rotate_left,
+            Action::Rotate180 => &mut self.rotate_180,
+            Action::Hold => &mut self.hold,
+            Action::Pause => &mut self.pause,
+            Action::Restart => &mut self.restart,
+        }
+    }
+
+    // The action's keys as "Up/X", leaving out any `is_known` says no to, for
+    // front ends that can't read them all.
+    fn shown_keys(&self, action: Action, is_known: &dyn Fn(&str) -> bool) -> String {
+        let keys: Vec<&str> = self.keys(action).iter().map(|key| key.as_str()).filter(|&key| is_known(key)).collect();
+        keys.join("/")
+    }
+
+    // What the keys do, for the help text, like "Z to rotate left": moving
+    // the pieces, then pausing and restarting if `with_game_keys`.
+    pub fn help(&self, is_known: &dyn Fn(&str) -> bool, with_game_keys: bool) -> Vec<String> {
+        let mut lines = Vec::new();
+        let (left, right) = (self.shown_keys(Action::MoveLeft, is_known), self.shown_keys(Action::MoveRight, is_known));
+        let actions = if with_game_keys { &ACTIONS[..] } else { &ACTIONS[..8] };
+        for &action in actions {
+            let keys = self.shown_keys(action, is_known);
+            match action {
+                _ if keys.is_empty() => {}
+                Action::MoveLeft if !right.is_empty() => lines.push(format!("{}, {} to move", left, right)),
+                Action::MoveRight if !left.is_empty() => {}
+                _ => lines.push(format!("{} to {}", keys, action.description())),
+            }
+        }
+        lines
+    }
+}
+
+// Which keys do what: one set for single player, including versus against
+// the computer, and one for each player sharing the keyboard in versus. The
+// look picked in the menu is kept with them.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    // Before the sets, as TOML has values ahead of tables.
+    pub theme: String,
+    // The theme's own glyphs without them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glyphs: Option<Glyphs>,
+    pub single: ActionKeys,
+    pub player1: ActionKeys,
+    pub player2: ActionKeys,
+}
+
+impl Default for Controls {
+    // Arrow keys, with Z, X and A to rotate as in the guideline games. In
+    // versus, player 1 is on WASD and player 2 on the arrow keys. Player 2
+    // holds with Right Shift, or / in a terminal.
+    fn default() -> Self {
+        Controls {
+            theme: "plain".to_string(),
+            glyphs: None,
+            single: ActionKeys {
+                move_left: names(&["Left"]),
+                move_right: names(&["Right"]),
+                soft_drop: names(&["Down"]),
+                hard_drop: names(&["Space"]),
+                rotate_right: names(&["Up", "X"]),
+                rotate_left: names(&["Z"]),
+                rotate_180: names(&["A"]),
+                hold: names(&["C", "Left Shift"]),
+                pause: names(&["Tab"]),
+                restart: names(&["R"]),
+            },
+            player1: ActionKeys {
+                move_left: names(&["A"]),
+                move_right: names(&["D"]),
+                soft_drop: names(&["S"]),
+                hard_drop: names(&["Space"]),
+                rotate_right: names(&["W"]),
+                rotate_left: names(&["Z"]),
+                rotate_180: Vec::new(),
+                hold: names(&["Q"]),
+                pause: names(&["Tab"]),
+                restart: names(&["R"]),
+            },
+            player2: ActionKeys {
+                move_left: names(&["Left"]),
+                move_right: names(&["Right"]),
+                soft_drop: names(&["Down"]),
+                hard_drop: names(&["Return"]),
+                rotate_right: names(&["Up"]),
+                rotate_left: names(&["Right Ctrl"]),
+                rotate_180: Vec::new(),
+                hold: names(&["Right Shift", "/"]),
+                pause: Vec::new(),
+                restart: Vec::new(),
+            },
+        }
+    }
+}
+
+impl Controls {
+    // The defaults if there is no such file. Sets left out of the file are
+    // the defaults too, but actions left out of a set have no keys.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut text = String::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_string(&mut text).map_err(|e| format!("Failed to read {}: {}", path, e))?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Controls::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+        let controls: Controls = toml::from_str(&text).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        for keys in [&controls.single, &controls.player1, &controls.player2].iter() {
+            for &action in ACTIONS.iter() {
+                for name in keys.keys(action) {
+                    check_key(name).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                }
+            }
+        }
+        Ok(controls)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        File::create(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    // The keys of each player at the keyboard.
+    pub fn players(&self, game: &Game) -> Vec<&ActionKeys> {
+        match *game {
+            Game::Versus(_, None) => vec![&self.player1, &self.player2],
+            _ => vec![&self.single],
+        }
+    }
+}
+
+// What the settings page of the menu can change.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Setting {
+    Theme,
+    Glyphs,
+}
+
+pub static SETTINGS: [Setting; 2] = [Setting::Theme, Setting::Glyphs];
+
+static GLYPHS: [Option<Glyphs>; 4] = [None, Some(Glyphs::None), Some(Glyphs::Letters), Some(Glyphs::Patterns)];
+
+impl Setting {
+    pub fn name(&self) -> &str {
+        match *self {
+            Setting::Theme => "Theme",
+            Setting::Glyphs => "Glyphs",
+        }
+    }
+
+    pub fn value(&self, controls: &Controls) -> String {
+        match *self {
+            Setting::Theme => controls.theme.clone(),
+            Setting::Glyphs => controls.glyphs.as_ref().map_or("the theme's", |glyphs| glyphs.name()).to_string(),
+        }
+    }
+}
+
+// The next of `values` after `value`, going round at the end, or the first
+// if `value` isn't one of them.
+fn next<T: Clone + PartialEq>(values: &[T], value: &T) -> T {
+    let i = values.iter().position(|v| v == value).map_or(0, |i| (i + 1) % values.len());
+    values[i].clone()
+}
+
+static PAGES: [&str; 4] = ["Single player", "Versus player 1", "Versus player 2", "Settings"];
+
+// Rebinding keys in the game: a page for each set of keys, with a row for
+// each action, then a page of settings.
+pub struct ControlsMenu {
+    pub page: usize,
+    pub row: usize,
+    // Waiting for a key to add to the row's action.
+    pub is_waiting: bool,
+    // The theme names to go through.
+    pub themes: Vec<String>,
+}
+
+impl Default for ControlsMenu {
+    fn default() -> Self {
+        ControlsMenu::new()
+    }
+}
+
+impl ControlsMenu {
+    pub fn new() -> Self {
+        ControlsMenu {
+            page: 0,
+            row: 0,
+            is_waiting: false,
+            themes: theme::names(),
+        }
+    }
+
+    pub fn is_settings_page(&self) -> bool {
+        self.page == PAGES.len() - 1
+    }
+
+    fn rows(&self) -> usize {
+        if self.is_settings_page() { SETTINGS.len() } else { ACTIONS.len() }
+    }
+
+    pub fn page_name(&self) -> &str {
+        PAGES[self.page]
+    }
+
+    pub fn keys<'a>(&self, controls: &'a Controls) -> &'a ActionKeys {
+        match self.page {
+            0 => &controls.single,
+            1 => &controls.player1,
+            _ => &controls.player2,
+        }
+    }
+
+    fn keys_mut<'a>(&self, controls: &'a mut Controls) -> &'a mut ActionKeys {
+        match self.page {
+            0 => &mut controls.single,
+            1 => &mut controls.player1,
+            _ => &mut controls.player2,
+        }
+    }
+
+    pub fn action(&self) -> Action {
+        ACTIONS[self.row]
+    }
+
+    pub fn setting(&self) -> Setting {
+        SETTINGS[self.row]
+    }
+
+    // Up and down the rows, and across the pages, going round at the ends.
+    // Pages with fewer rows keep to their last.
+    pub fn move_by(&mut self, rows: i32, pages: i32) {
+        self.page = (self.page as i32 + pages).rem_euclid(PAGES.len() as i32) as usize;
+        self.row = self.row.min(self.rows() - 1);
+        self.row = (self.row as i32 + rows).rem_euclid(self.rows() as i32) as usize;
+    }
+
+    // Waits for a key to add to the row's action, or changes the row's
+    // setting to the next choice.
+    pub fn select(&mut self, controls: &mut Controls) {
+        if !self.is_settings_page() {
+            self.is_waiting = true;
+            return;
+        }
+        match self.setting() {
+            Setting::Theme => controls.theme = next(&self.themes, &controls.theme),
+            Setting::Glyphs => controls.glyphs = next(&GLYPHS, &controls.glyphs),
+        }
+    }
+
+    // Keeps waiting for another key if the game keeps this one.
+    pub fn add_key(&mut self, controls: &mut Controls, name: &str) -> Result<(), String> {
+        check_key(name)?;
+        let action = self.action();
+        let keys = self.keys_mut(controls).keys_mut(action);
+        if !keys.iter().any(|key| key.eq_ignore_ascii_case(name)) {
+            keys.push(name.to_string());
+        }
+        self.is_waiting = false;
+        Ok(())
+    }
+
+    pub fn clear_keys(&self, controls: &mut Controls) {
+        if self.is_settings_page() {
+            return;
+        }
+        let action = self.action();
+        self.keys_mut(controls).keys_mut(action).clear();
+    }
+}
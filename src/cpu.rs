@@ -0,0 +1,131 @@
+use rand::{Rng, SeedableRng, StdRng};
+
+use ai::{self, Field, Move, Weights};
+use super::{Controller, Input, TetrisBoard, TetrisPiece};
+
+// How well a CPU plays. Harder ones are faster, look further ahead and make
+// fewer mistakes.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Difficulty {
+    pub name: &'static str,
+    pub pieces_per_second: f64,
+    // Preview pieces to look ahead at.
+    pub lookahead: usize,
+    // Chance of going for a random placement instead of the best one.
+    pub mistake_rate: f64,
+}
+
+impl Difficulty {
+    // Easy, medium or hard, medium for anything else.
+    pub fn named(name: &str) -> Self {
+        let (name, pieces_per_second, lookahead, mistake_rate) = match name {
+            "easy" => ("CPU (Easy)", 0.5, 0, 0.2),
+            "hard" => ("CPU (Hard)", 2.5, 2, 0.0),
+            _ => ("CPU", 1.0, 1, 0.05),
+        };
+        Difficulty {
+            name: name,
+            pieces_per_second: pieces_per_second,
+            lookahead: lookahead,
+            mistake_rate: mistake_rate,
+        }
+    }
+}
+
+// A computer player, pressing the same keys a human would.
+pub struct Cpu {
+    pub difficulty: Difficulty,
+    weights: Weights,
+    // For the mistakes, so seeded games play out the same.
+    rng: StdRng,
+    target: Option<TetrisPiece>,
+    pieces_locked: Option<u32>,
+    input_delay: u32,
+    input_countdown: u32,
+}
+
+impl Cpu {
+    pub fn from_difficulty(difficulty: &str) -> Self {
+        Cpu::new(Difficulty::named(difficulty), StdRng::new().unwrap())
+    }
+
+    pub fn with_seed(difficulty: &str, seed: usize) -> Self {
+        Cpu::new(Difficulty::named(difficulty), StdRng::from_seed(&[seed][..]))
+    }
+
+    fn new(difficulty: Difficulty, rng: StdRng) -> Self {
+        Cpu {
+            difficulty: difficulty,
+            weights: Weights::new(),
+            rng: rng,
+            target: None,
+            pieces_locked: None,
+            input_delay: 0,
+            input_countdown: 0,
+        }
+    }
+
+    fn choose_target(&mut self, board: &TetrisBoard) {
+        let field = Field::from_board(board);
+        let preview = board.tetris_gen.preview(self.difficulty.lookahead, board.spawn_pos);
+        let placement = if self.rng.gen::<f64>() < self.difficulty.mistake_rate {
+            let mut ranked = ai::rank_placements(&field, &board.active_piece, &preview, &self.weights);
+            if ranked.is_empty() {
+                None
+            } else {
+                let i = self.rng.gen_range(0, ranked.len());
+                Some(ranked.swap_remove(i))
+            }
+        } else {
+            ai::best_placement(&field, &board.active_piece, &preview, &self.weights)
+        };
+
+        self.target = placement.map(|placement| {
+            // Spread the key presses out over the time we have for the piece.
+            let frames_per_piece = (60.0 / self.difficulty.pieces_per_second) as u32;
+            self.input_delay = frames_per_piece / placement.moves.len() as u32;
+            self.input_countdown = self.input_delay;
+            placement.piece
+        });
+    }
+}
+
+impl Controller for Cpu {
+    fn name(&self) -> &str {
+        self.difficulty.name
+    }
+
+    fn next_input(&mut self, board: &TetrisBoard) -> Input {
+        let mut input = Input::new();
+        if board.is_game_over || !board.is_piece_active() {
+            return input;
+        }
+
+        if self.pieces_locked != Some(board.pieces_locked) {
+            self.pieces_locked = Some(board.pieces_locked);
+            self.choose_target(board);
+        }
+
+        if self.input_countdown > 0 {
+            self.input_countdown -= 1;
+            return input;
+        }
+        self.input_countdown = self.input_delay;
+
+        // Work out the way there from wherever the piece is now, as gravity
+        // may have moved it since the last key press.
+        let next_move = self.target
+            .and_then(|target| ai::path_to(&Field::from_board(board), &board.active_piece, &target))
+            .map_or(Move::HardDrop, |moves| moves[0]);
+        match next_move {
+            Move::Left => input.left_key_pressed = true,
+            Move::Right => input.right_key_pressed = true,
+            Move::RotateRight => input.up_key_pressed = true,
+            Move::RotateLeft => input.rotate_left_key_pressed = true,
+            Move::Rotate180 => input.rotate_180_key_pressed = true,
+            Move::SoftDrop => input.down_key_pressed = true,
+            Move::HardDrop => input.hard_drop_key_pressed = true,
+        }
+        input
+    }
+}
@@ -0,0 +1,119 @@
+use rand::{Rng, SeedableRng, StdRng};
+
+use super::{Input, TetrisBoard};
+
+// Dig race: clear a fixed number of garbage rows as fast as possible.
+// The board starts with some garbage and more is pushed up from the bottom
+// as the player digs through it.
+pub struct DigGame {
+    pub board: TetrisBoard,
+    pub goal: u32,
+    start_rows: u32,
+    // Chance (0.0 - 1.0) that a garbage row's hole moves away from the
+    // hole in the row below it.
+    messiness: f64,
+    garbage_inserted: u32,
+    last_hole: usize,
+    rng: StdRng,
+    pub frames: u32,
+    pub is_complete: bool,
+}
+
+impl DigGame {
+    pub fn new(goal: u32, start_rows: u32, messiness: f64) -> Self {
+        DigGame::build(goal, start_rows, messiness, TetrisBoard::new(), StdRng::new().unwrap())
+    }
+
+    // The same pieces and garbage every time for the same seed.
+    pub fn with_seed(goal: u32, start_rows: u32, messiness: f64, seed: usize) -> Self {
+        DigGame::build(goal, start_rows, messiness, TetrisBoard::with_seed(seed), StdRng::from_seed(&[seed][..]))
+    }
+
+    fn build(goal: u32, start_rows: u32, messiness: f64, board: TetrisBoard, rng: StdRng) -> Self {
+        let mut dig_game = DigGame {
+            board: board,
+            goal: goal,
+            start_rows: start_rows,
+            messiness: messiness,
+            garbage_inserted: 0,
+            last_hole: 0,
+            rng: rng,
+            frames: 0,
+            is_complete: false,
+        };
+        dig_game.fill_garbage();
+        dig_game
+    }
+
+    pub fn restart(&mut self) {
+        self.board.reset();
+        self.garbage_inserted = 0;
+        self.frames = 0;
+        self.is_complete = false;
+        self.fill_garbage();
+    }
+
+    pub fn garbage_cleared(&self) -> u32 {
+        self.garbage_inserted - self.board.garbage_rows()
+    }
+
+    fn next_hole(&mut self) -> usize {
+        let width = self.board.field_width();
+        if self.garbage_inserted == 0 {
+            return self.rng.gen_range(0, width);
+        }
+        if self.rng.gen::<f64>() < self.messiness {
+            // Pick any column other than the one below.
+            let hole = self.rng.gen_range(0, width - 1);
+            if hole >= self.last_hole { hole + 1 } else { hole }
+        } else {
+            self.last_hole
+        }
+    }
+
+    // Keep start_rows rows of garbage on the board until the whole goal
+    // has been sent.
+    fn fill_garbage(&mut self) {
+        while self.garbage_inserted < self.goal && self.board.garbage_rows() < self.start_rows {
+            let hole = self.next_hole();
+            self.last_hole = hole;
+            self.garbage_inserted += 1;
+            if !self.board.insert_garbage_row(hole) {
+                return;
+            }
+        }
+    }
+
+    pub fn update(&mut self, input: &Input) {
+        if self.is_complete {
+            if input.up_key_pressed {
+                self.restart();
+            }
+            return;
+        }
+
+        let was_game_over = self.board.is_game_over;
+        let pieces_locked = self.board.pieces_locked;
+
+        self.board.update(input);
+
+        if was_game_over {
+            // The board resets itself on restart, so only the garbage needs
+            // to come back.
+            if !self.board.is_game_over {
+                self.restart();
+            }
+            return;
+        }
+
+        self.frames += 1;
+
+        if self.board.pieces_locked != pieces_locked && !self.board.is_game_over {
+            if self.garbage_cleared() >= self.goal {
+                self.is_complete = true;
+            } else {
+                self.fill_garbage();
+            }
+        }
+    }
+}
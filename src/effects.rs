@@ -0,0 +1,523 @@
+use std::cmp;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use frame::{Block, BoardView, Frame, Panel, Particle, Text, TextSize};
+use game::Game;
+use super::{Color, Pos2D, TetrisBoard, TetrisUnitBlock};
+
+// How long each effect lasts, in frames at 60 a second. Cleared rows flash
+// white for the first few frames, then their blocks vanish one by one.
+static CLEAR_FRAMES: u32 = 20;
+static FLASH_FRAMES: u32 = 6;
+static LOCK_FRAMES: u32 = 8;
+static SPARK_FRAMES: u32 = 40;
+static TEXT_FRAMES: u32 = 60;
+static SHAKE_FRAMES: u32 = 16;
+
+// Attacks this big or bigger shake the screen.
+static BIG_ATTACK: u32 = 4;
+
+static WHITE: Color = Color { r: 255, g: 255, b: 255 };
+static YELLOW: Color = Color { r: 255, g: 255, b: 0 };
+static ORANGE: Color = Color { r: 255, g: 128, b: 0 };
+static CYAN: Color = Color { r: 0, g: 255, b: 255 };
+static PURPLE: Color = Color { r: 200, g: 100, b: 255 };
+static GREEN: Color = Color { r: 0, g: 255, b: 0 };
+
+// Which effects to show.
+#[derive(Copy, Clone)]
+pub struct EffectSettings {
+    pub clears: bool,
+    pub locks: bool,
+    pub particles: bool,
+    pub shake: bool,
+    pub text: bool,
+}
+
+impl EffectSettings {
+    pub fn all() -> Self {
+        EffectSettings {
+            clears: true,
+            locks: true,
+            particles: true,
+            shake: true,
+            text: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        EffectSettings {
+            clears: false,
+            locks: false,
+            particles: false,
+            shake: false,
+            text: false,
+        }
+    }
+
+    // "all", "none", or some of clears, locks, particles, shake and text,
+    // separated by commas.
+    pub fn from_names(names: &str) -> Result<Self, String> {
+        match names {
+            "all" => return Ok(EffectSettings::all()),
+            "none" => return Ok(EffectSettings::none()),
+            _ => {}
+        }
+        let mut settings = EffectSettings::none();
+        for name in names.split(',') {
+            match name.trim() {
+                "clears" => settings.clears = true,
+                "locks" => settings.locks = true,
+                "particles" => settings.particles = true,
+                "shake" => settings.shake = true,
+                "text" => settings.text = true,
+                other => return Err(format!("Unknown effect {}", other)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+// The stack as it was when rows were cleared, drawn instead of the board
+// while it keeps the full rows, in modes with a line clear delay. Without one
+// they have already gone, along with the effect, as a stack from before would
+// hide pieces that lock after.
+struct LineClear {
+    blocks: Vec<Vec<Option<Block>>>,
+    // The cleared rows' blocks, and the frame each one vanishes on.
+    vanishing: Vec<(Pos2D, u32)>,
+    // The active piece, drawn over the stack.
+    piece: Vec<(Pos2D, Block)>,
+    age: u32,
+}
+
+struct LockFlash {
+    blocks: Vec<Pos2D>,
+    age: u32,
+}
+
+// In blocks from the board's top left, moving by `dx` and `dy` each frame.
+struct Spark {
+    x: f32,
+    y: f32,
+    dx: f32,
+    dy: f32,
+    color: Color,
+    age: u32,
+}
+
+struct FloatingText {
+    lines: Vec<(String, Color)>,
+    age: u32,
+}
+
+struct Shake {
+    // How far the screen moves at first, in blocks.
+    strength: f32,
+    age: u32,
+}
+
+// A board as of the last update, to tell what changed, and its effects.
+struct BoardEffects {
+    cells: Vec<Vec<TetrisUnitBlock>>,
+    pieces_locked: u32,
+    level: Option<u32>,
+    lines_sent: Option<u32>,
+    combo: Option<u32>,
+    is_back_to_back: bool,
+    clear: Option<LineClear>,
+    flash: Option<LockFlash>,
+    sparks: Vec<Spark>,
+    text: Option<FloatingText>,
+}
+
+// Where a cell is drawn in a board view, in blocks, as (x, y, width, height).
+fn cell_span(board: &TetrisBoard, x: usize, y: usize) -> (i32, i32, i32, i32) {
+    let (left, columns) = board.column_span(x);
+    let (top, rows) = board.row_span(y);
+    (left, top, columns, rows)
+}
+
+// Every block a cell is drawn as.
+fn view_blocks(board: &TetrisBoard, x: usize, y: usize) -> Vec<Pos2D> {
+    let mut blocks = Vec::new();
+    if x < board.width && y < board.height {
+        let (left, top, columns, rows) = cell_span(board, x, y);
+        for dy in 0..rows {
+            for dx in 0..columns {
+                blocks.push(Pos2D::xy(left + dx, top + dy));
+            }
+        }
+    }
+    blocks
+}
+
+// "Tetris", "T-Spin Double" and so on, if the lock was worth a name.
+fn clear_name(lines: u32, is_t_spin: bool) -> Option<String> {
+    let name = match lines {
+        0 => String::new(),
+        1 => "Single".to_string(),
+        2 => "Double".to_string(),
+        3 => "Triple".to_string(),
+        4 => "Tetris".to_string(),
+        _ => format!("{} Lines", lines),
+    };
+    match (is_t_spin, lines) {
+        (true, 0) => Some("T-Spin".to_string()),
+        (true, _) => Some(format!("T-Spin {}", name)),
+        (false, 0) => None,
+        (false, _) => Some(name),
+    }
+}
+
+fn faded(color: Color, life: u32, fade: u32) -> Color {
+    let life = cmp::min(life, fade);
+    Color::RGB((color.r as u32 * life / fade) as u8, (color.g as u32 * life / fade) as u8, (color.b as u32 * life / fade) as u8)
+}
+
+impl BoardEffects {
+    fn new(board: &TetrisBoard, level: Option<u32>, lines_sent: Option<u32>) -> Self {
+        BoardEffects {
+            cells: board.board.clone(),
+            pieces_locked: board.pieces_locked,
+            level: level,
+            lines_sent: lines_sent,
+            combo: None,
+            is_back_to_back: false,
+            clear: None,
+            flash: None,
+            sparks: Vec::new(),
+            text: None,
+        }
+    }
+
+    // Returns how hard to shake the screen, if at all.
+    fn update(&mut self, board: &TetrisBoard, level: Option<u32>, lines_sent: Option<u32>, settings: &EffectSettings,
+              rng: &mut StdRng) -> Option<f32> {
+        self.age();
+
+        // The board started over.
+        if board.pieces_locked < self.pieces_locked {
+            *self = BoardEffects::new(board, level, lines_sent);
+            return None;
+        }
+
+        let mut shake = None;
+        if board.pieces_locked != self.pieces_locked {
+            self.pieces_locked = board.pieces_locked;
+            shake = self.lock(board, settings, rng);
+        }
+
+        // Versus attacks shake the screen however they were made.
+        if let (Some(before), Some(after)) = (self.lines_sent, lines_sent) {
+            if after >= before + BIG_ATTACK && settings.shake {
+                shake = Some(cmp::min(after - before, 10) as f32 * 0.08);
+            }
+        }
+        self.lines_sent = lines_sent;
+
+        if let (Some(before), Some(after)) = (self.level, level) {
+            if after > before && settings.text {
+                let line = (format!("Level {}", after), WHITE);
+                match self.text {
+                    Some(ref mut text) if text.age == 0 => text.lines.push(line),
+                    _ => self.text = Some(FloatingText { lines: vec![line], age: 0 }),
+                }
+            }
+        }
+        self.level = level;
+
+        if board.count_full_rows() == 0 {
+            self.clear = None;
+        }
+        if let Some(ref mut clear) = self.clear {
+            clear.piece.clear();
+            if board.is_piece_active() && !board.is_game_over {
+                let piece = &board.active_piece;
+                let block = Block::new(piece.color, piece.kind);
+                for cell in piece.iter() {
+                    if cell.x >= 0 && cell.y >= 0 {
+                        for pos in view_blocks(board, cell.x as usize, cell.y as usize) {
+                            clear.piece.push((pos, block));
+                        }
+                    }
+                }
+            }
+        }
+
+        self.cells = board.board.clone();
+        shake
+    }
+
+    fn age(&mut self) {
+        if let Some(ref mut clear) = self.clear {
+            clear.age += 1;
+        }
+        if let Some(ref mut flash) = self.flash {
+            flash.age += 1;
+        }
+        if self.flash.as_ref().is_some_and(|flash| flash.age >= LOCK_FRAMES) {
+            self.flash = None;
+        }
+        for spark in self.sparks.iter_mut() {
+            spark.x += spark.dx;
+            spark.y += spark.dy;
+            // Gravity.
+            spark.dy += 0.03;
+            spark.age += 1;
+        }
+        self.sparks.retain(|spark| spark.age < SPARK_FRAMES);
+        if let Some(ref mut text) = self.text {
+            text.age += 1;
+        }
+        if self.text.as_ref().is_some_and(|text| text.age >= TEXT_FRAMES) {
+            self.text = None;
+        }
+    }
+
+    // The piece that just locked, against the stack from before it did.
+    fn lock(&mut self, board: &TetrisBoard, settings: &EffectSettings, rng: &mut StdRng) -> Option<f32> {
+        let piece = board.last_locked_piece?;
+        let lines = board.last_clear_lines;
+        let is_t_spin = board.last_lock_was_t_spin;
+
+        let mut stack = self.cells.clone();
+        let mut piece_cells = Vec::new();
+        for cell in piece.iter() {
+            if cell.x < 0 || cell.y < 0 {
+                continue;
+            }
+            let (x, y) = (cell.x as usize, cell.y as usize);
+            if let Some(stacked) = stack.get_mut(y).and_then(|row| row.get_mut(x)) {
+                *stacked = TetrisUnitBlock { is_filled: true, color: piece.color, is_garbage: false, kind: Some(piece.kind) };
+                piece_cells.push((x, y));
+            }
+        }
+        let field_columns = 1..board.width - 1;
+        let full_rows: Vec<usize> = (1..board.height - 1)
+            .filter(|&y| field_columns.clone().all(|x| stack[y][x].is_filled))
+            .collect();
+        let is_perfect_clear = lines > 0 && (1..board.height - 1)
+            .all(|y| full_rows.contains(&y) || field_columns.clone().all(|x| !stack[y][x].is_filled));
+        let cleared_cells: Vec<(usize, usize)> = full_rows.iter()
+            .flat_map(|&y| field_columns.clone().map(move |x| (x, y)))
+            .collect();
+
+        // The same combo and back-to-back as versus, for every mode.
+        let is_difficult = lines >= 4 || is_t_spin;
+        let is_back_to_back = lines > 0 && is_difficult && self.is_back_to_back;
+        if lines == 0 {
+            self.combo = None;
+        } else {
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+            self.is_back_to_back = is_difficult;
+        }
+
+        if settings.locks {
+            let blocks = piece_cells.iter().flat_map(|&(x, y)| view_blocks(board, x, y)).collect();
+            self.flash = Some(LockFlash { blocks: blocks, age: 0 });
+        }
+
+        if settings.clears && board.count_full_rows() > 0 {
+            let mut blocks = Vec::new();
+            for (y, row) in stack.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    let (left, top, columns, rows) = cell_span(board, x, y);
+                    let right = (left + columns) as usize;
+                    let bottom = (top + rows) as usize;
+                    if blocks.len() < bottom {
+                        blocks.resize(bottom, Vec::new());
+                    }
+                    for drawn in blocks[top as usize..bottom].iter_mut() {
+                        if drawn.len() < right {
+                            drawn.resize(right, None);
+                        }
+                        for block in drawn[left as usize..right].iter_mut() {
+                            *block = if cell.is_filled { Some(Block::from_cell(cell)) } else { None };
+                        }
+                    }
+                }
+            }
+            let vanishing = cleared_cells.iter()
+                .flat_map(|&(x, y)| view_blocks(board, x, y))
+                .map(|pos| (pos, rng.gen_range(FLASH_FRAMES + 1, CLEAR_FRAMES)))
+                .collect();
+            self.clear = Some(LineClear {
+                blocks: blocks,
+                vanishing: vanishing,
+                piece: Vec::new(),
+                age: 0,
+            });
+        }
+
+        if settings.particles && (lines >= 4 || is_t_spin) {
+            // Out of the cleared rows, or the piece for a T-spin that
+            // cleared none.
+            let sources = if cleared_cells.is_empty() { &piece_cells } else { &cleared_cells };
+            for &(x, y) in sources.iter() {
+                let (left, top, columns, rows) = cell_span(board, x, y);
+                for _ in 0..2 {
+                    self.sparks.push(Spark {
+                        x: left as f32 + columns as f32 / 2.0,
+                        y: top as f32 + rows as f32 / 2.0,
+                        dx: rng.gen_range(-0.3, 0.3),
+                        dy: rng.gen_range(-0.6, -0.1),
+                        color: stack[y][x].color,
+                        age: 0,
+                    });
+                }
+            }
+        }
+
+        if settings.text {
+            let mut text = Vec::new();
+            if let Some(name) = clear_name(lines, is_t_spin) {
+                let color = if is_t_spin { PURPLE } else if lines >= 4 { CYAN } else { WHITE };
+                text.push((name, color));
+            }
+            if is_back_to_back {
+                text.push(("Back-to-Back".to_string(), ORANGE));
+            }
+            if let Some(combo) = self.combo {
+                if combo > 0 {
+                    text.push((format!("{} Combo", combo), YELLOW));
+                }
+            }
+            if is_perfect_clear {
+                text.push(("Perfect Clear!".to_string(), GREEN));
+            }
+            if !text.is_empty() {
+                self.text = Some(FloatingText { lines: text, age: 0 });
+            }
+        }
+
+        // Versus shakes for the attack instead.
+        let attack = if is_t_spin { lines * 2 } else { lines };
+        if settings.shake && self.lines_sent.is_none() && attack >= BIG_ATTACK {
+            Some(cmp::min(attack, 10) as f32 * 0.08)
+        } else {
+            None
+        }
+    }
+
+    fn apply(&self, view: &mut BoardView, particles: &mut Vec<Particle>, panels: &mut Vec<Panel>) {
+        if let Some(ref clear) = self.clear {
+            if clear.blocks.len() == view.blocks.len() {
+                view.blocks = clear.blocks.clone();
+                for &(pos, vanish_at) in clear.vanishing.iter() {
+                    let drawn = &mut view.blocks[pos.y as usize][pos.x as usize];
+                    if clear.age >= vanish_at {
+                        *drawn = None;
+                    } else if let Some(ref mut block) = *drawn {
+                        block.glow = if clear.age < FLASH_FRAMES { 255 } else { 128 };
+                    }
+                }
+                for &(pos, block) in clear.piece.iter() {
+                    if let Some(drawn) = view.blocks.get_mut(pos.y as usize).and_then(|row| row.get_mut(pos.x as usize)) {
+                        *drawn = Some(block);
+                    }
+                }
+            }
+        }
+
+        if let Some(ref flash) = self.flash {
+            let glow = (192 * (LOCK_FRAMES - flash.age) / LOCK_FRAMES) as u8;
+            for pos in flash.blocks.iter() {
+                if let Some(&mut Some(ref mut block)) = view.blocks.get_mut(pos.y as usize).and_then(|row| row.get_mut(pos.x as usize)) {
+                    block.glow = cmp::max(block.glow, glow);
+                }
+            }
+        }
+
+        for spark in self.sparks.iter() {
+            particles.push(Particle {
+                x: view.pos.x as f32 + spark.x,
+                y: view.pos.y as f32 + spark.y,
+                color: faded(spark.color, SPARK_FRAMES - spark.age, SPARK_FRAMES / 2),
+            });
+        }
+
+        // Rising up the board as it fades out.
+        if let Some(ref text) = self.text {
+            let life = TEXT_FRAMES - text.age;
+            panels.push(Panel {
+                pos: Pos2D::xy(view.pos.x + 1, view.pos.y + view.height() / 3 - (text.age / 20) as i32),
+                lines: text.lines.iter().map(|&(ref line, color)| Text {
+                    text: line.clone(),
+                    size: TextSize::Medium,
+                    color: faded(color, life, TEXT_FRAMES / 3),
+                }).collect(),
+            });
+        }
+    }
+}
+
+// Flashes, particles and the like for what happens in a game: line clears,
+// locks, level ups and big attacks. They only change how frames look, never
+// the game, so replays play the same with or without them.
+pub struct Effects {
+    pub settings: EffectSettings,
+    // Hides them all, without forgetting which are on.
+    pub show: bool,
+    boards: Vec<BoardEffects>,
+    shake: Option<Shake>,
+    // Seeded, so exported replays come out the same every time.
+    rng: StdRng,
+}
+
+impl Effects {
+    pub fn new(settings: EffectSettings) -> Self {
+        Effects {
+            settings: settings,
+            show: true,
+            boards: Vec::new(),
+            shake: None,
+            rng: StdRng::from_seed(&[0][..]),
+        }
+    }
+
+    // Called after each update of the game.
+    pub fn update(&mut self, game: &Game) {
+        let boards: Vec<(&TetrisBoard, Option<u32>)> = match *game {
+            Game::Versus(ref versus_game, _) => versus_game.players.iter()
+                .map(|player| (&player.board, Some(player.lines_sent)))
+                .collect(),
+            _ => game.board().map(|board| (board, None)).into_iter().collect(),
+        };
+        let level = game.level();
+
+        if self.boards.len() != boards.len() {
+            self.boards = boards.iter().map(|&(board, lines_sent)| BoardEffects::new(board, level, lines_sent)).collect();
+        }
+
+        if let Some(ref mut shake) = self.shake {
+            shake.age += 1;
+        }
+        if self.shake.as_ref().is_some_and(|shake| shake.age >= SHAKE_FRAMES) {
+            self.shake = None;
+        }
+        for (effects, &(board, lines_sent)) in self.boards.iter_mut().zip(boards.iter()) {
+            if let Some(strength) = effects.update(board, level, lines_sent, &self.settings, &mut self.rng) {
+                self.shake = Some(Shake { strength: strength, age: 0 });
+            }
+        }
+    }
+
+    // Draws the effects into a frame of the game.
+    pub fn apply(&self, frame: &mut Frame) {
+        if !self.show || frame.boards.len() != self.boards.len() {
+            return;
+        }
+        for (effects, view) in self.boards.iter().zip(frame.boards.iter_mut()) {
+            effects.apply(view, &mut frame.particles, &mut frame.panels);
+        }
+        // Back and forth, dying down.
+        if let Some(ref shake) = self.shake {
+            let distance = shake.strength * (SHAKE_FRAMES - shake.age) as f32 / SHAKE_FRAMES as f32;
+            let x = if shake.age / 2 % 2 == 0 { distance } else { -distance };
+            let y = if shake.age / 3 % 2 == 0 { distance / 2.0 } else { -distance / 2.0 };
+            frame.shake = (x, y);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::cmp;
+use std::fs::File;
+use std::io::BufWriter;
+
+use gif::{self, SetParameter};
+use png;
+
+enum Encoder {
+    // With the delay of each frame, in hundredths of a second.
+    Gif(gif::Encoder<BufWriter<File>>, u16),
+    Apng(png::Writer<BufWriter<File>>),
+}
+
+// Writes an animation a frame at a time, as a GIF, or as an APNG for a .png
+// or .apng file. Frames are RGB pixels, `width` by `height`, resized by
+// `scale` on the way out.
+pub struct AnimationWriter {
+    encoder: Encoder,
+    width: u32,
+    height: u32,
+    scaled_width: u32,
+    scaled_height: u32,
+}
+
+impl AnimationWriter {
+    // Each frame stays up for `frame_skip` frames of the game, at 60 a second.
+    // APNGs need to know how many frames there will be.
+    pub fn create(path: &str, width: u32, height: u32, scale: f64, frame_skip: u32, frames: u32) -> Result<Self, String> {
+        let scaled_width = cmp::max((width as f64 * scale) as u32, 1);
+        let scaled_height = cmp::max((height as f64 * scale) as u32, 1);
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        if !["gif", "png", "apng"].contains(&extension.as_str()) {
+            return Err(format!("Unknown format for {}, expected .gif, .png or .apng", path));
+        }
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let file = BufWriter::new(file);
+        let encoder = match extension.as_str() {
+            "gif" => {
+                if scaled_width > u16::MAX as u32 || scaled_height > u16::MAX as u32 {
+                    return Err("Too big for a GIF".to_string());
+                }
+                let mut encoder = gif::Encoder::new(file, scaled_width as u16, scaled_height as u16, &[])
+                    .map_err(|e| e.to_string())?;
+                encoder.set(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+                // GIFs only count in hundredths, so this is as close as they get.
+                let delay = cmp::max((frame_skip * 100 + 30) / 60, 1);
+                Encoder::Gif(encoder, delay as u16)
+            }
+            "png" | "apng" => {
+                let mut encoder = png::Encoder::new(file, scaled_width, scaled_height);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(frames, 0).map_err(|e| e.to_string())?;
+                encoder.set_frame_delay(frame_skip as u16, 60).map_err(|e| e.to_string())?;
+                Encoder::Apng(encoder.write_header().map_err(|e| e.to_string())?)
+            }
+            _ => unreachable!(),
+        };
+        Ok(AnimationWriter {
+            encoder: encoder,
+            width: width,
+            height: height,
+            scaled_width: scaled_width,
+            scaled_height: scaled_height,
+        })
+    }
+
+    // Rows of `pixels` start `pitch` bytes apart, like an SDL surface's.
+    pub fn write_frame(&mut self, pixels: &[u8], pitch: usize) -> Result<(), String> {
+        let mut scaled = Vec::with_capacity((self.scaled_width * self.scaled_height * 3) as usize);
+        for y in 0..self.scaled_height {
+            let row = &pixels[(y * self.height / self.scaled_height) as usize * pitch..];
+            for x in 0..self.scaled_width {
+                let i = (x * self.width / self.scaled_width) as usize * 3;
+                scaled.extend_from_slice(&row[i..i + 3]);
+            }
+        }
+        match self.encoder {
+            Encoder::Gif(ref mut encoder, delay) => {
+                let mut frame = gif::Frame::from_rgb_speed(self.scaled_width as u16, self.scaled_height as u16, &scaled, 10);
+                frame.delay = delay;
+                encoder.write_frame(&frame).map_err(|e| e.to_string())
+            }
+            Encoder::Apng(ref mut writer) => writer.write_image_data(&scaled).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self.encoder {
+            // The trailer is written when the encoder is dropped.
+            Encoder::Gif(..) => Ok(()),
+            Encoder::Apng(writer) => writer.finish().map_err(|e| e.to_string()),
+        }
+    }
+}
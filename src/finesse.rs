@@ -0,0 +1,184 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+
+use ai::Field;
+use super::{Input, PieceKind, Pos2D, TetrisBoard, TetrisPiece};
+
+// A single key press: a tap moves one column, holding a direction (DAS)
+// slides the piece to the wall or the stack, and the rotation keys turn it
+// either way or halfway round.
+#[derive(Copy, Clone)]
+enum Press {
+    TapLeft,
+    TapRight,
+    DasLeft,
+    DasRight,
+    RotateRight,
+    RotateLeft,
+    Rotate180,
+}
+
+static PRESSES: [Press; 7] = [Press::TapLeft, Press::TapRight, Press::DasLeft, Press::DasRight,
+                              Press::RotateRight, Press::RotateLeft, Press::Rotate180];
+
+fn apply_press(field: &Field, piece: &TetrisPiece, press: Press) -> Option<TetrisPiece> {
+    let mut moved = *piece;
+    let step = match press {
+        Press::TapLeft | Press::DasLeft => Pos2D::xy(-1, 0),
+        Press::TapRight | Press::DasRight => Pos2D::xy(1, 0),
+        Press::RotateRight => return piece.rotated_right(|rotated| field.fits(rotated)),
+        Press::RotateLeft => return piece.rotated_left(|rotated| field.fits(rotated)),
+        // Two turns right, kicks and all, as the board does it.
+        Press::Rotate180 => {
+            return piece.rotated_right(|rotated| field.fits(rotated))
+                .and_then(|half| half.rotated_right(|rotated| field.fits(rotated)));
+        }
+    };
+    moved.move_by(step);
+    if !field.fits(&moved) {
+        return None;
+    }
+    if let Press::DasLeft | Press::DasRight = press {
+        loop {
+            let mut next = moved;
+            next.move_by(step);
+            if !field.fits(&next) {
+                break;
+            }
+            moved = next;
+        }
+    }
+    Some(moved)
+}
+
+fn cells_key(piece: &TetrisPiece) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = piece.iter().map(|pos| (pos.x, pos.y)).collect();
+    cells.sort();
+    cells
+}
+
+// The fewest key presses that line the piece up above where it locked, so a
+// hard drop puts it there. None for tucks and spins, which can't be reached
+// by dropping straight down.
+pub fn minimal_inputs(field: &Field, spawn: &TetrisPiece, locked: &TetrisPiece) -> Option<u32> {
+    if !field.fits(spawn) {
+        return None;
+    }
+    let target = cells_key(locked);
+    let mut presses = HashMap::new();
+    let mut queue = VecDeque::new();
+    presses.insert((spawn.pos.x, spawn.orientation), 0);
+    queue.push_back(*spawn);
+
+    while let Some(current) = queue.pop_front() {
+        let count = presses[&(current.pos.x, current.orientation)];
+        if cells_key(&field.drop(&current)) == target {
+            return Some(count);
+        }
+        for &press in PRESSES.iter() {
+            if let Some(next) = apply_press(field, &current, press) {
+                if let Entry::Vacant(entry) = presses.entry((next.pos.x, next.orientation)) {
+                    entry.insert(count + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    None
+}
+
+pub struct PieceFinesse {
+    pub number: u32,
+    pub kind: PieceKind,
+    pub inputs: u32,
+    pub minimal: u32,
+}
+
+impl PieceFinesse {
+    pub fn is_fault(&self) -> bool {
+        self.inputs > self.minimal
+    }
+}
+
+// Counts the keys pressed for each piece and compares them to the fewest
+// that would have done.
+pub struct FinesseTracker {
+    // The board and piece as the current piece spawned.
+    field: Option<Field>,
+    spawn: Option<TetrisPiece>,
+    inputs: u32,
+    pieces_locked: u32,
+    is_hold_used: bool,
+    pub pieces: Vec<PieceFinesse>,
+    pub faults: u32,
+}
+
+impl Default for FinesseTracker {
+    fn default() -> Self {
+        FinesseTracker::new()
+    }
+}
+
+impl FinesseTracker {
+    pub fn new() -> Self {
+        FinesseTracker {
+            field: None,
+            spawn: None,
+            inputs: 0,
+            pieces_locked: 0,
+            is_hold_used: false,
+            pieces: Vec::new(),
+            faults: 0,
+        }
+    }
+
+    // Called after each board update with the input that went into it.
+    pub fn update(&mut self, board: &TetrisBoard, input: &Input) {
+        // The board started over.
+        if board.pieces_locked < self.pieces_locked {
+            *self = FinesseTracker::new();
+        }
+
+        if self.field.is_some() {
+            self.inputs += [(input.left_key_pressed, input.left_is_repeat),
+                            (input.right_key_pressed, input.right_is_repeat),
+                            (input.up_key_pressed, input.up_is_repeat),
+                            (input.rotate_left_key_pressed, input.rotate_left_is_repeat),
+                            (input.rotate_180_key_pressed, input.rotate_180_is_repeat)].iter()
+                .filter(|&&(pressed, is_repeat)| pressed && !is_repeat)
+                .count() as u32;
+        }
+
+        if board.pieces_locked != self.pieces_locked {
+            self.pieces_locked = board.pieces_locked;
+            if let (Some(field), Some(spawn), Some(locked)) = (self.field.take(), self.spawn.take(), board.last_locked_piece) {
+                if let Some(minimal) = minimal_inputs(&field, &spawn, &locked) {
+                    let piece = PieceFinesse {
+                        number: board.pieces_locked,
+                        kind: locked.kind,
+                        inputs: self.inputs,
+                        minimal: minimal,
+                    };
+                    if piece.is_fault() {
+                        self.faults += 1;
+                    }
+                    self.pieces.push(piece);
+                }
+            }
+            self.inputs = 0;
+        }
+
+        // A held piece's keys don't count; the piece swapped in starts afresh.
+        if board.is_hold_used && !self.is_hold_used {
+            self.field = None;
+            self.spawn = None;
+            self.inputs = 0;
+        }
+        self.is_hold_used = board.is_hold_used;
+
+        if self.field.is_none() && board.is_piece_active() && !board.is_game_over {
+            self.field = Some(Field::from_board(board));
+            self.spawn = Some(board.active_piece);
+        }
+    }
+}
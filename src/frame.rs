@@ -0,0 +1,469 @@
+use controls::{Controls, ControlsMenu, ACTIONS, SETTINGS};
+use finesse::FinesseTracker;
+use game::{format_frames, Game};
+use pc;
+use versus::Player;
+use super::{Color, PieceKind, Pos2D, TetrisBoard, TetrisPiece, TetrisUnitBlock};
+
+// What the screen shows for a frame of the game, laid out once for every
+// front end to draw. Positions are in blocks, on a screen SCREEN_WIDTH blocks
+// wide and SCREEN_HEIGHT tall. Each line of text takes a row, or two when
+// it's large.
+pub static SCREEN_WIDTH: i32 = 40;
+pub static SCREEN_HEIGHT: i32 = 30;
+
+static WHITE: Color = Color { r: 255, g: 255, b: 255 };
+static RED: Color = Color { r: 255, g: 0, b: 0 };
+static DARK_RED: Color = Color { r: 128, g: 0, b: 0 };
+static GREEN: Color = Color { r: 0, g: 255, b: 0 };
+static DARK_GREEN: Color = Color { r: 0, g: 128, b: 0 };
+static YELLOW: Color = Color { r: 255, g: 255, b: 0 };
+static DARK_YELLOW: Color = Color { r: 128, g: 128, b: 0 };
+static ORANGE: Color = Color { r: 255, g: 128, b: 0 };
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl TextSize {
+    pub fn rows(&self) -> i32 {
+        match *self {
+            TextSize::Large => 2,
+            _ => 1,
+        }
+    }
+}
+
+pub struct Text {
+    pub text: String,
+    pub size: TextSize,
+    pub color: Color,
+}
+
+// Lines of text, one under the other.
+pub struct Panel {
+    pub pos: Pos2D,
+    pub lines: Vec<Text>,
+}
+
+impl Panel {
+    fn at(x: i32, y: i32) -> Self {
+        Panel {
+            pos: Pos2D::xy(x, y),
+            lines: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str, size: TextSize, color: Color) {
+        self.lines.push(Text {
+            text: text.to_string(),
+            size: size,
+            color: color,
+        });
+    }
+}
+
+// What a block is, for themes that draw each kind differently.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Tile {
+    Piece(PieceKind),
+    Garbage,
+    Wall,
+}
+
+impl Tile {
+    // How themes refer to it: the piece's letter, "garbage" or "wall".
+    pub fn name(&self) -> String {
+        match *self {
+            Tile::Piece(kind) => kind.letter().to_string(),
+            Tile::Garbage => "garbage".to_string(),
+            Tile::Wall => "wall".to_string(),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Block {
+    pub color: Color,
+    pub tile: Tile,
+    // Where a piece should go, rather than a piece.
+    pub is_ghost: bool,
+    // How far it is lit up toward white, for effects.
+    pub glow: u8,
+}
+
+impl Block {
+    pub fn new(color: Color, kind: PieceKind) -> Self {
+        Block { color: color, tile: Tile::Piece(kind), is_ghost: false, glow: 0 }
+    }
+
+    // A filled cell of a board.
+    pub fn from_cell(cell: &TetrisUnitBlock) -> Self {
+        let tile = match cell.kind {
+            Some(kind) => Tile::Piece(kind),
+            None if cell.is_garbage => Tile::Garbage,
+            None => Tile::Wall,
+        };
+        Block { color: cell.color, tile: tile, is_ghost: false, glow: 0 }
+    }
+}
+
+pub struct BoardView {
+    pub pos: Pos2D,
+    // The blocks as drawn, walls included, with the active piece on top.
+    pub blocks: Vec<Vec<Option<Block>>>,
+    // Rows of incoming garbage, shown in the column left of the board.
+    pub incoming: Option<u32>,
+}
+
+impl BoardView {
+    fn new(board: &TetrisBoard, pos: Pos2D, ghost: Option<&TetrisPiece>) -> Self {
+        let (right, wall) = board.column_span(board.width - 1);
+        let (bottom, floor) = board.row_span(board.height - 1);
+        let mut blocks = vec![vec![None; (right + wall) as usize]; (bottom + floor) as usize];
+        {
+            // Big mode's cells take up more than one block.
+            let mut fill = |cell: Pos2D, block: Block| {
+                let (left, columns) = board.column_span(cell.x as usize);
+                let (top, rows) = board.row_span(cell.y as usize);
+                for row in blocks[top as usize..(top + rows) as usize].iter_mut() {
+                    for drawn in row[left as usize..(left + columns) as usize].iter_mut() {
+                        *drawn = Some(block);
+                    }
+                }
+            };
+            for (y, row) in board.board.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    if cell.is_filled {
+                        fill(Pos2D::xy(x as i32, y as i32), Block::from_cell(cell));
+                    }
+                }
+            }
+            if let Some(ghost) = ghost {
+                for cell in ghost.iter() {
+                    fill(cell, Block { is_ghost: true, ..Block::new(ghost.color, ghost.kind) });
+                }
+            }
+            if board.is_piece_active() {
+                for cell in board.active_piece.iter() {
+                    fill(cell, Block::new(board.active_piece.color, board.active_piece.kind));
+                }
+            }
+        }
+        BoardView {
+            pos: pos,
+            blocks: blocks,
+            incoming: None,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.blocks.first().map_or(0, |row| row.len() as i32)
+    }
+
+    pub fn height(&self) -> i32 {
+        self.blocks.len() as i32
+    }
+}
+
+// A piece on its own, like the held one. Its cells are around `pos`.
+pub struct PieceView {
+    pub pos: Pos2D,
+    pub block: Block,
+    pub cells: Vec<Pos2D>,
+}
+
+impl PieceView {
+    fn new(piece: &TetrisPiece, pos: Pos2D) -> Self {
+        PieceView {
+            pos: pos,
+            block: Block::new(piece.color, piece.kind),
+            cells: piece.blocks().to_vec(),
+        }
+    }
+}
+
+// A speck thrown off by an effect, at a point rather than on a block.
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub color: Color,
+}
+
+pub struct Frame {
+    pub boards: Vec<BoardView>,
+    pub pieces: Vec<PieceView>,
+    pub panels: Vec<Panel>,
+    pub particles: Vec<Particle>,
+    // How far everything is moved, in blocks, when the screen shakes.
+    pub shake: (f32, f32),
+}
+
+impl Frame {
+    // `help` says which keys do what, a few lines for each player at the
+    // keyboard. Games start over with the keys `controls` has for rotating
+    // right.
+    pub fn new(game: &Game, finesse: &FinesseTracker, controls: &Controls, help: &[Vec<String>]) -> Self {
+        let mut frame = Frame::empty();
+        let restart_keys: Vec<&str> = controls.players(game).iter()
+            .flat_map(|keys| keys.rotate_right.first())
+            .map(|key| key.as_str())
+            .collect();
+        let restart_keys = restart_keys.join(" or ");
+        match game.board() {
+            Some(board) => frame.single_player(game, board, finesse, help, &restart_keys),
+            None => frame.versus(game, help, &restart_keys),
+        }
+        frame
+    }
+
+    fn empty() -> Self {
+        Frame {
+            boards: Vec::new(),
+            pieces: Vec::new(),
+            panels: Vec::new(),
+            particles: Vec::new(),
+            shake: (0.0, 0.0),
+        }
+    }
+
+    // The menu for rebinding keys, in place of the game. `instructions` say
+    // which keys work it.
+    pub fn controls_menu(controls: &Controls, menu: &ControlsMenu, instructions: &[String]) -> Self {
+        let mut frame = Frame::empty();
+        let mut title = Panel::at(15, 1);
+        title.push("Controls", TextSize::Large, WHITE);
+        frame.panels.push(title);
+
+        let mut page = Panel::at(2, 5);
+        page.push(&format!("< {} >", menu.page_name()), TextSize::Medium, YELLOW);
+        frame.panels.push(page);
+
+        let mut names = Panel::at(2, 7);
+        let mut keys = Panel::at(10, 7);
+        if menu.is_settings_page() {
+            for (row, setting) in SETTINGS.iter().enumerate() {
+                let color = if row == menu.row { YELLOW } else { WHITE };
+                names.push(setting.name(), TextSize::Medium, color);
+                keys.push(&setting.value(controls), TextSize::Medium, color);
+            }
+        } else {
+            for (row, &action) in ACTIONS.iter().enumerate() {
+                let bound = menu.keys(controls).keys(action);
+                let (text, color) = if row != menu.row {
+                    (bound.join(", "), WHITE)
+                } else if menu.is_waiting {
+                    ("Press a key to add".to_string(), ORANGE)
+                } else {
+                    (bound.join(", "), YELLOW)
+                };
+                names.push(action.name(), TextSize::Medium, color);
+                keys.push(if text.is_empty() { "-" } else { &text }, TextSize::Medium, color);
+            }
+        }
+        frame.panels.push(names);
+        frame.panels.push(keys);
+
+        let mut help = Panel::at(2, 19);
+        for line in instructions {
+            help.push(line, TextSize::Small, WHITE);
+        }
+        frame.panels.push(help);
+        frame
+    }
+
+    // Over the game while it's paused.
+    pub fn pause(&mut self, message: &str) {
+        self.banner("PAUSED", YELLOW, message, DARK_YELLOW);
+    }
+
+    // A big message over the middle of the board.
+    fn banner(&mut self, title: &str, color: Color, message: &str, message_color: Color) {
+        let mut panel = Panel::at(14, 13);
+        panel.push(title, TextSize::Large, color);
+        self.panels.push(panel);
+        let mut panel = Panel::at(7, 15);
+        panel.push(message, TextSize::Large, message_color);
+        self.panels.push(panel);
+    }
+
+    fn single_player(&mut self, game: &Game, board: &TetrisBoard, finesse: &FinesseTracker, help: &[Vec<String>],
+                     restart_keys: &str) {
+        let board_pos = Pos2D::xy(13, 2);
+        let ghost = match *game {
+            Game::Pc(ref pc_game) if pc_game.show_hints && board.is_piece_active() => pc_game.hint().map(|step| step.piece),
+            _ => None,
+        };
+        self.boards.push(BoardView::new(board, board_pos, ghost.as_ref()));
+
+        let mut keys = Panel::at(1, 0);
+        for line in help.first().map_or(&[][..], |lines| &lines[..]) {
+            keys.push(line, TextSize::Small, WHITE);
+        }
+        keys.push(&format!("Finesse faults : {}", finesse.faults), TextSize::Small, WHITE);
+        self.panels.push(keys);
+        let restart = format!("Press {} to restart", restart_keys);
+        let retry = format!("Press {} to retry", restart_keys);
+
+        let mut title = Panel::at(15, 0);
+        title.push("Tetris", TextSize::Large, WHITE);
+        self.panels.push(title);
+
+        let mut hold = Panel::at(8, 20);
+        hold.push("Hold", TextSize::Medium, WHITE);
+        self.panels.push(hold);
+        if let Some(kind) = board.hold_piece {
+            self.pieces.push(PieceView::new(&TetrisPiece::build(kind, Pos2D::xy(0, 0)), Pos2D::xy(9, 22)));
+        }
+
+        if game.is_finished() {
+            let mut results = Panel::at(0, 8);
+            results.push(&format!("Finesse : {} of {} pieces", finesse.faults, finesse.pieces.len()), TextSize::Small, WHITE);
+            for piece in finesse.pieces.iter().rev().filter(|piece| piece.is_fault()).take(10) {
+                results.push(&format!("#{} {} : {} keys, {} needed", piece.number, piece.kind.letter(), piece.inputs, piece.minimal),
+                             TextSize::Small, ORANGE);
+            }
+            self.panels.push(results);
+        }
+
+        let mut stats = Panel::at(26, 0);
+        stats.push(&format!("Lines : {}", board.lines_cleared), TextSize::Large, WHITE);
+        match *game {
+            Game::Marathon(ref board) => {
+                stats.push(&format!("Level : {}", board.level), TextSize::Large, WHITE);
+                stats.push(&format!("Score : {}", board.score), TextSize::Large, WHITE);
+            }
+            Game::Dig(ref dig_game) => {
+                stats.push(&format!("Level : {}", dig_game.board.level), TextSize::Large, WHITE);
+                stats.push(&format!("Garbage : {}/{}", dig_game.garbage_cleared(), dig_game.goal), TextSize::Large, WHITE);
+                stats.push(&format!("Time : {}", format_frames(dig_game.frames)), TextSize::Large, WHITE);
+                if dig_game.is_complete {
+                    self.banner("CLEARED!", GREEN, &restart, DARK_GREEN);
+                }
+            }
+            Game::Master(ref master_game) => {
+                stats.push(&format!("Level : {}/{}", master_game.level, master_game.section_goal()), TextSize::Large, WHITE);
+                stats.push(&format!("Grade : {}", master_game.grade()), TextSize::Large, WHITE);
+                stats.push(&format!("Score : {}", master_game.score), TextSize::Large, WHITE);
+                stats.push(&format!("Time : {}", format_frames(master_game.frames)), TextSize::Large, WHITE);
+                for (i, section_time) in master_game.section_times.iter().enumerate() {
+                    stats.push(&format!("{:03} - {:03} : {}", i * 100, i * 100 + 99, format_frames(*section_time)), TextSize::Small, WHITE);
+                }
+                if master_game.is_complete {
+                    self.banner("CLEARED!", GREEN, &restart, DARK_GREEN);
+                }
+            }
+            Game::Puzzle(ref puzzle_game) => {
+                stats.push(&puzzle_game.puzzle.name, TextSize::Medium, WHITE);
+                stats.push(&format!("Goal : {}", puzzle_game.puzzle.goal.description()), TextSize::Medium, WHITE);
+                stats.push(&format!("Pieces left : {}", puzzle_game.pieces_left()), TextSize::Medium, WHITE);
+                stats.push(&format!("Attempt : {}", puzzle_game.attempts), TextSize::Medium, WHITE);
+                stats.push("R to retry", TextSize::Small, WHITE);
+                if puzzle_game.is_solved {
+                    self.banner("SOLVED!", GREEN, &retry, DARK_GREEN);
+                } else if puzzle_game.is_failed && !puzzle_game.board.is_game_over {
+                    self.banner("FAILED", RED, &retry, DARK_RED);
+                }
+            }
+            Game::Pc(ref pc_game) => {
+                stats.push(&format!("Perfect clears : {}", pc_game.perfect_clears), TextSize::Medium, WHITE);
+                stats.push(&format!("Misses : {}", pc_game.misses), TextSize::Medium, WHITE);
+                let status = if pc_game.is_solving() {
+                    "Solving...".to_string()
+                } else if pc_game.solutions.is_empty() {
+                    "No perfect clear in view".to_string()
+                } else {
+                    format!("{} solutions", pc_game.solutions.len())
+                };
+                stats.push(&status, TextSize::Medium, WHITE);
+                stats.push(if pc_game.show_hints { "H to hide hints" } else { "H to show hints" }, TextSize::Small, WHITE);
+                if pc_game.is_missed {
+                    stats.push("Off every solution!", TextSize::Medium, ORANGE);
+                }
+
+                let mut next = Panel::at(26, 9);
+                next.push("Next", TextSize::Medium, WHITE);
+                self.panels.push(next);
+                for (i, piece) in pc_game.board.tetris_gen.preview(pc::PREVIEW_SIZE, Pos2D::xy(0, 0)).iter().enumerate() {
+                    self.pieces.push(PieceView::new(piece, Pos2D::xy(27, 11 + 3 * i as i32)));
+                }
+
+                if let Some(step) = ghost {
+                    if step.kind != board.active_piece.kind {
+                        let mut cue = Panel::at(8, 19);
+                        cue.push("Hold!", TextSize::Medium, YELLOW);
+                        self.panels.push(cue);
+                    }
+                }
+            }
+            Game::Versus(..) => {}
+        }
+        self.panels.push(stats);
+
+        if board.is_game_over {
+            self.banner("GAME OVER!", RED, &restart, DARK_RED);
+        }
+    }
+
+    fn versus(&mut self, game: &Game, help: &[Vec<String>], restart_keys: &str) {
+        let (versus_game, cpu) = match *game {
+            Game::Versus(ref versus_game, ref cpu) => (versus_game, cpu),
+            _ => return,
+        };
+        let names = ["P1", cpu.as_ref().map_or("P2", |cpu| cpu.name())];
+        let board_x = [2, 26];
+        for (i, player) in versus_game.players.iter().enumerate() {
+            self.player(player, names[i], board_x[i]);
+        }
+
+        // The keys, between the boards.
+        let mut keys = Panel::at(15, 3);
+        for (i, lines) in help.iter().enumerate() {
+            if i > 0 {
+                keys.push("", TextSize::Small, WHITE);
+            }
+            keys.push(names[i], TextSize::Medium, WHITE);
+            for line in lines {
+                keys.push(line, TextSize::Small, WHITE);
+            }
+        }
+        self.panels.push(keys);
+
+        if versus_game.is_finished {
+            let result = match versus_game.winner {
+                Some(winner) => format!("{} WINS!", names[winner]),
+                None => "DRAW!".to_string(),
+            };
+            let mut panel = Panel::at(15, 22);
+            panel.push(&result, TextSize::Large, YELLOW);
+            panel.push(&format!("Press {}", restart_keys), TextSize::Small, DARK_YELLOW);
+            panel.push("to restart", TextSize::Small, DARK_YELLOW);
+            self.panels.push(panel);
+        }
+    }
+
+    fn player(&mut self, player: &Player, name: &str, x: i32) {
+        let mut header = Panel::at(x, 0);
+        header.push(&format!("{}  Sent : {}", name, player.lines_sent), TextSize::Medium, WHITE);
+        let mut status = Vec::new();
+        if let Some(combo) = player.combo {
+            if combo > 0 {
+                status.push(format!("{} Combo", combo));
+            }
+        }
+        if player.is_back_to_back {
+            status.push("Back-to-Back".to_string());
+        }
+        header.push(&status.join("  "), TextSize::Small, YELLOW);
+        let hold = player.board.hold_piece.map_or("-".to_string(), |kind| kind.letter().to_string());
+        header.push(&format!("Hold : {}", hold), TextSize::Small, WHITE);
+        self.panels.push(header);
+
+        let mut view = BoardView::new(&player.board, Pos2D::xy(x, 3), None);
+        view.incoming = Some(player.incoming_total() * player.board.block_scale);
+        self.boards.push(view);
+    }
+}
@@ -0,0 +1,427 @@
+// Fumen v115, the board diagram format shared between Tetris tools.
+// https://harddrop.com/fumen/
+//
+// A fumen string is a list of pages, each a field, a piece and a comment,
+// packed into base64 digits. Fields are stored as run lengths of the
+// difference from the previous page's field.
+
+use tbp::{self, PieceLocation};
+use pieces::{self, piece_set};
+use super::{Color, PieceKind, RandomTetrisPieceGenerator, TetrisBoard, TetrisUnitBlock};
+
+static BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+static COMMENT_CHARS: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+static FIELD_WIDTH: usize = 10;
+// Rows above the floor, then one more for the garbage row under it.
+static FIELD_TOP: usize = 23;
+static FIELD_ROWS: usize = 24;
+static FIELD_BLOCKS: usize = 240;
+
+// Fumen's cell values, for an empty cell and garbage.
+static EMPTY: u8 = 0;
+static GRAY: u8 = 8;
+
+// Fumen's rotation numbers, by our TBP orientation names.
+static ROTATIONS: [&str; 4] = ["south", "east", "north", "west"];
+
+// Pages with unchanged fields after this are stored as a count.
+static MAX_REPEAT: usize = 63;
+
+static KINDS: [PieceKind; 7] = [PieceKind::I, PieceKind::L, PieceKind::O, PieceKind::Z, PieceKind::T, PieceKind::J, PieceKind::S];
+
+// Fumen only has the seven tetrominoes.
+fn kind_number(kind: PieceKind) -> Option<u8> {
+    KINDS.iter().position(|&k| k == kind).map(|i| i as u8 + 1)
+}
+
+fn number_kind(number: u8) -> Option<PieceKind> {
+    if (1..=7).contains(&number) { Some(KINDS[number as usize - 1]) } else { None }
+}
+
+// Fumen puts the O, I, S and Z somewhere else than SRS does in some
+// orientations. This is what to add to fumen's position to get SRS's.
+fn srs_offset(kind: PieceKind, rotation: &str) -> (i32, i32) {
+    match (kind, rotation) {
+        (PieceKind::O, "west") => (1, -1),
+        (PieceKind::O, "south") => (1, 0),
+        (PieceKind::O, "north") => (0, -1),
+        (PieceKind::I, "south") => (1, 0),
+        (PieceKind::I, "west") => (0, -1),
+        (PieceKind::S, "north") => (0, -1),
+        (PieceKind::S, "east") => (-1, 0),
+        (PieceKind::Z, "north") => (0, -1),
+        (PieceKind::Z, "west") => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+pub struct Page {
+    // FIELD_ROWS rows of FIELD_WIDTH cells, top first, the last being the
+    // garbage row under the floor.
+    pub field: Vec<Vec<u8>>,
+    pub piece: Option<PieceLocation>,
+    pub comment: Option<String>,
+    // Whether the piece is put into the field for the next page.
+    pub lock: bool,
+    // Whether the garbage row is pushed up into the field for the next page.
+    pub rise: bool,
+    pub mirror: bool,
+}
+
+fn empty_field() -> Vec<Vec<u8>> {
+    vec![vec![EMPTY; FIELD_WIDTH]; FIELD_ROWS]
+}
+
+fn block_color(value: u8) -> Color {
+    match number_kind(value).and_then(|kind| piece_set().get(kind)) {
+        Some(piece) => piece.color,
+        None => Color::RGB(128, 128, 128),
+    }
+}
+
+impl Page {
+    // The board's stack and active piece. Our board has one row fewer, so
+    // fumen's top row is always empty.
+    pub fn from_board(board: &TetrisBoard, comment: Option<String>) -> Result<Self, String> {
+        pieces::require_tetrominoes("Fumens")?;
+        let mut field = empty_field();
+        for (row, blocks) in field.iter_mut().zip(board.board.iter()).take(board.height - 1).skip(1) {
+            for (value, block) in row.iter_mut().zip(blocks[1..board.width - 1].iter()) {
+                *value = if !block.is_filled {
+                    EMPTY
+                } else if block.is_garbage {
+                    GRAY
+                } else {
+                    block.kind.and_then(kind_number).unwrap_or(GRAY)
+                };
+            }
+        }
+        Ok(Page {
+            field: field,
+            piece: if board.is_piece_active() { tbp::piece_to_location(&board.active_piece) } else { None },
+            comment: comment,
+            lock: true,
+            rise: false,
+            mirror: false,
+        })
+    }
+
+    // This page's stack, top to bottom, as our board's rows.
+    pub fn stack(&self) -> Vec<Vec<TetrisUnitBlock>> {
+        self.field[1..FIELD_TOP].iter().map(|row| {
+            row.iter().map(|&value| TetrisUnitBlock {
+                is_filled: value != EMPTY,
+                color: if value == EMPTY { Color::RGB(0, 0, 0) } else { block_color(value) },
+                is_garbage: value == GRAY,
+                kind: number_kind(value),
+            }).collect()
+        }).collect()
+    }
+
+    // A new board with this page's stack, and its piece as the active piece.
+    pub fn to_board(&self) -> TetrisBoard {
+        let mut board = TetrisBoard::with_setup(&self.stack(), RandomTetrisPieceGenerator::new());
+        if let Some(piece) = self.piece.as_ref().and_then(tbp::location_to_piece) {
+            board.active_piece = piece;
+        }
+        board
+    }
+
+    // Cells of the piece in the field, as (row, column).
+    fn piece_cells(&self) -> Vec<(usize, usize)> {
+        let piece = match self.piece.as_ref().and_then(tbp::location_to_piece) {
+            Some(piece) => piece,
+            None => return Vec::new(),
+        };
+        // Our row numbers line up with fumen's.
+        piece.iter()
+            .filter(|pos| pos.y >= 0 && (pos.y as usize) < FIELD_TOP && pos.x >= 1 && pos.x as usize <= FIELD_WIDTH)
+            .map(|pos| (pos.y as usize, pos.x as usize - 1))
+            .collect()
+    }
+
+    // What the field looks like at the start of the next page.
+    fn next_field(&self) -> Vec<Vec<u8>> {
+        let mut field = self.field.clone();
+        if self.lock {
+            if let Some(value) = self.piece.as_ref().and_then(|piece| PieceKind::from_letter(&piece.kind)).and_then(kind_number) {
+                for (row, x) in self.piece_cells() {
+                    field[row][x] = value;
+                }
+            }
+            let mut rows: Vec<Vec<u8>> = field[..FIELD_TOP].iter()
+                .filter(|row| row.contains(&EMPTY))
+                .cloned()
+                .collect();
+            while rows.len() < FIELD_TOP {
+                rows.insert(0, vec![EMPTY; FIELD_WIDTH]);
+            }
+            rows.push(field[FIELD_TOP].clone());
+            field = rows;
+            if self.rise {
+                let garbage = field[FIELD_TOP].clone();
+                field.remove(0);
+                field.insert(FIELD_TOP - 1, garbage);
+                field[FIELD_TOP] = vec![EMPTY; FIELD_WIDTH];
+            }
+            if self.mirror {
+                for row in field[..FIELD_TOP].iter_mut() {
+                    row.reverse();
+                }
+            }
+        }
+        field
+    }
+}
+
+fn push_value(data: &mut String, mut value: usize, digits: usize) {
+    for _ in 0..digits {
+        data.push(BASE64[value % 64] as char);
+        value /= 64;
+    }
+}
+
+// JavaScript's escape(), which fumen runs comments through.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match unit {
+            0x30..=0x39 | 0x41..=0x5a | 0x61..=0x7a => escaped.push(unit as u8 as char),
+            _ if "@*_+-./".contains(unit as u8 as char) && unit < 0x80 => escaped.push(unit as u8 as char),
+            _ if unit < 0x100 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let hex = |start: usize, len: usize| -> Option<u16> {
+            if start + len > chars.len() {
+                return None;
+            }
+            let digits: String = chars[start..start + len].iter().collect();
+            u16::from_str_radix(&digits, 16).ok()
+        };
+        if chars[i] == '%' {
+            if chars.get(i + 1) == Some(&'u') {
+                if let Some(unit) = hex(i + 2, 4) {
+                    units.push(unit);
+                    i += 6;
+                    continue;
+                }
+            } else if let Some(unit) = hex(i + 1, 2) {
+                units.push(unit);
+                i += 3;
+                continue;
+            }
+        }
+        units.push(chars[i] as u16);
+        i += 1;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+fn push_comment(data: &mut String, comment: &str) {
+    let escaped: Vec<usize> = escape(comment).chars()
+        .map(|c| COMMENT_CHARS.find(c).unwrap_or(0))
+        .take(4095)
+        .collect();
+    push_value(data, escaped.len(), 2);
+    for chunk in escaped.chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, &c| value * 96 + c);
+        push_value(data, value, 5);
+    }
+}
+
+pub fn encode(pages: &[Page]) -> String {
+    let mut data = String::new();
+    let mut previous_field = empty_field();
+    let mut previous_comment: Option<String> = None;
+    // Where the count of repeated unchanged fields goes, and the count so far.
+    let mut repeat: Option<(usize, usize)> = None;
+
+    for page in pages {
+        let diffs: Vec<usize> = page.field.iter().zip(previous_field.iter())
+            .flat_map(|(row, previous_row)| row.iter().zip(previous_row.iter())
+                 .map(|(&value, &previous)| (value as usize + 8) - previous as usize)
+                 .collect::<Vec<_>>())
+            .collect();
+
+        let is_unchanged = diffs.iter().all(|&diff| diff == 8);
+        match repeat {
+            Some((at, count)) if is_unchanged && count < MAX_REPEAT => {
+                repeat = Some((at, count + 1));
+            }
+            _ => {
+                if let Some((at, count)) = repeat.take() {
+                    data.replace_range(at..at + 1, &(BASE64[count] as char).to_string());
+                }
+                let mut start = 0;
+                while start < diffs.len() {
+                    let end = (start..diffs.len()).find(|&j| diffs[j] != diffs[start]).unwrap_or(diffs.len());
+                    push_value(&mut data, diffs[start] * FIELD_BLOCKS + (end - start - 1), 2);
+                    start = end;
+                }
+                if is_unchanged {
+                    repeat = Some((data.len(), 0));
+                    data.push(BASE64[0] as char);
+                }
+            }
+        }
+
+        let (kind, rotation, location) = match page.piece.as_ref().and_then(|piece| {
+            PieceKind::from_letter(&piece.kind).and_then(|kind| kind_number(kind).map(|number| (kind, number, piece)))
+        }) {
+            Some((kind, number, piece)) => {
+                let rotation = ROTATIONS.iter().position(|&r| r == piece.orientation).unwrap_or(2);
+                let (dx, dy) = srs_offset(kind, &piece.orientation);
+                let (x, y) = (piece.x - dx, piece.y - dy);
+                let location = (FIELD_TOP as i32 - 1 - y) * FIELD_WIDTH as i32 + x;
+                (number as usize, rotation, location.max(0) as usize % FIELD_BLOCKS)
+            }
+            None => (0, 0, 0),
+        };
+        let has_comment = page.comment != previous_comment;
+        // Pages are always marked as using guideline colors.
+        let flags = [!page.lock, has_comment, true, page.mirror, page.rise];
+        let mut action = flags.iter().fold(0, |value, &flag| value * 2 + flag as usize);
+        action = ((action * FIELD_BLOCKS + location) * 4 + rotation) * 8 + kind;
+        push_value(&mut data, action, 3);
+
+        if has_comment {
+            push_comment(&mut data, page.comment.as_ref().map_or("", |comment| comment.as_str()));
+            previous_comment = page.comment.clone();
+        }
+        previous_field = page.next_field();
+    }
+    if let Some((at, count)) = repeat {
+        data.replace_range(at..at + 1, &(BASE64[count] as char).to_string());
+    }
+
+    // Long strings are broken up with ?s, which decoders skip.
+    let mut fumen = String::from("v115@");
+    for (i, c) in data.chars().enumerate() {
+        if i >= 42 && (i - 42) % 47 == 0 {
+            fumen.push('?');
+        }
+        fumen.push(c);
+    }
+    fumen
+}
+
+struct Reader {
+    digits: Vec<usize>,
+    position: usize,
+}
+
+impl Reader {
+    fn poll(&mut self, count: usize) -> Result<usize, String> {
+        if self.position + count > self.digits.len() {
+            return Err("Fumen data ended early".to_string());
+        }
+        let value = self.digits[self.position..self.position + count].iter().rev()
+            .fold(0, |value, &digit| value * 64 + digit);
+        self.position += count;
+        Ok(value)
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.digits.len()
+    }
+}
+
+// Takes a fumen string or a link to one.
+pub fn decode(fumen: &str) -> Result<Vec<Page>, String> {
+    pieces::require_tetrominoes("Fumens")?;
+    let start = fumen.find("115@").ok_or_else(|| "Not a v115 fumen".to_string())? + 4;
+    let digits = fumen[start..].chars()
+        .filter(|&c| c != '?' && !c.is_whitespace())
+        .map(|c| BASE64.iter().position(|&b| b as char == c).ok_or_else(|| format!("Bad fumen character {}", c)))
+        .collect::<Result<Vec<usize>, String>>()?;
+    let mut reader = Reader { digits: digits, position: 0 };
+
+    let mut pages = Vec::new();
+    let mut previous_field = empty_field();
+    let mut comment: Option<String> = None;
+    let mut repeat = 0;
+
+    while !reader.is_done() {
+        let mut field = previous_field.clone();
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut cell = 0;
+            while cell < FIELD_BLOCKS {
+                let value = reader.poll(2)?;
+                let (diff, count) = (value / FIELD_BLOCKS, value % FIELD_BLOCKS + 1);
+                if cell + count > FIELD_BLOCKS || diff > 16 {
+                    return Err("Bad fumen field".to_string());
+                }
+                for i in cell..cell + count {
+                    let cell_value = field[i / FIELD_WIDTH][i % FIELD_WIDTH] as i32 + diff as i32 - 8;
+                    field[i / FIELD_WIDTH][i % FIELD_WIDTH] = cell_value.max(0).min(GRAY as i32) as u8;
+                }
+                cell += count;
+                if count == FIELD_BLOCKS && diff == 8 {
+                    repeat = reader.poll(1)?;
+                }
+            }
+        }
+
+        let mut action = reader.poll(3)?;
+        let kind_value = action % 8;
+        action /= 8;
+        let rotation = ROTATIONS[action % 4];
+        action /= 4;
+        let location = action % FIELD_BLOCKS;
+        action /= FIELD_BLOCKS;
+        let rise = action % 2 == 1;
+        let mirror = action / 2 % 2 == 1;
+        let has_comment = action / 8 % 2 == 1;
+        let lock = action / 16 % 2 == 0;
+
+        if has_comment {
+            let length = reader.poll(2)?;
+            let mut escaped = String::new();
+            for _ in 0..length.div_ceil(4) {
+                let mut value = reader.poll(5)?;
+                for _ in 0..4 {
+                    escaped.push(COMMENT_CHARS.chars().nth(value % 96).unwrap_or(' '));
+                    value /= 96;
+                }
+            }
+            escaped.truncate(length);
+            comment = Some(unescape(&escaped));
+        }
+
+        let piece = number_kind(kind_value as u8).map(|kind| {
+            let (dx, dy) = srs_offset(kind, rotation);
+            let x = (location % FIELD_WIDTH) as i32;
+            let y = FIELD_TOP as i32 - 1 - (location / FIELD_WIDTH) as i32;
+            PieceLocation {
+                kind: kind.letter().to_string(),
+                orientation: rotation.to_string(),
+                x: x + dx,
+                y: y + dy,
+            }
+        });
+
+        let page = Page {
+            field: field,
+            piece: piece,
+            comment: comment.clone(),
+            lock: lock,
+            rise: rise,
+            mirror: mirror,
+        };
+        previous_field = page.next_field();
+        pages.push(page);
+    }
+    Ok(pages)
+}
@@ -0,0 +1,151 @@
+use std::cmp;
+
+use cpu::Cpu;
+use dig::DigGame;
+use fumen;
+use master::MasterGame;
+use pc::PcGame;
+use pieces::{self, PieceSet};
+use puzzle::{Puzzle, PuzzleGame};
+use sim::Ruleset;
+use tbp::ExternalBot;
+use versus::VersusGame;
+use super::{Controller, Input, TetrisBoard};
+
+// The modes the game can be played in, shared by the front ends. There's
+// only one at a time, so it isn't worth boxing the bigger ones.
+#[allow(clippy::large_enum_variant)]
+pub enum Game {
+    Marathon(TetrisBoard),
+    Dig(DigGame),
+    Master(MasterGame),
+    Puzzle(PuzzleGame),
+    Pc(PcGame),
+    // Player 2 is played by the computer or a TBP bot if there is a controller.
+    Versus(VersusGame, Option<Box<dyn Controller>>),
+}
+
+// Takes `name <value>` out of the arguments, for options that can go before
+// or after the mode.
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    let value = args.get(i + 1).cloned().unwrap_or_default();
+    args.drain(i..cmp::min(i + 2, args.len()));
+    Some(value)
+}
+
+// Loads the pieces given by `--pieces <file>`, or the standard ones, taking
+// those arguments out. Has to happen before any piece is made.
+pub fn load_pieces(args: &mut Vec<String>) -> Result<(), String> {
+    let pieces_path = take_option(args, "--pieces").unwrap_or("res/pieces/standard.toml".to_string());
+    pieces::use_piece_set(PieceSet::load(&pieces_path)?)
+}
+
+pub fn format_frames(frames: u32) -> String {
+    format!("{}.{:02}", frames / 60, (frames % 60) * 100 / 60)
+}
+
+impl Game {
+    pub fn from_args(args: &[String]) -> Self {
+        let arg = |i: usize, default: u32| -> u32 {
+            args.get(i).and_then(|s| s.parse().ok()).unwrap_or(default)
+        };
+        match args.get(1).map(|s| s.as_str()) {
+            Some("dig") => Game::Dig(DigGame::new(arg(2, 18), arg(3, 10), arg(4, 30) as f64 / 100.0)),
+            Some("master") => Game::Master(MasterGame::new()),
+            Some("puzzle") => {
+                let puzzle = Puzzle::load(args.get(2).map_or("", |s| s.as_str())).unwrap_or_else(|e| panic!("{}", e));
+                Game::Puzzle(PuzzleGame::new(puzzle))
+            }
+            Some("pc") => Game::Pc(PcGame::new().unwrap_or_else(|e| panic!("{}", e))),
+            Some("big") => Game::Marathon(TetrisBoard::big()),
+            Some("fumen") => {
+                let pages = fumen::decode(args.get(2).map_or("", |s| s.as_str())).expect("Failed to read the fumen");
+                Game::Marathon(pages.first().map_or_else(TetrisBoard::new, |page| page.to_board()))
+            }
+            Some("versus") => {
+                let cpu: Option<Box<dyn Controller>> = match args.get(2).map(|s| s.as_str()) {
+                    Some("cpu") => Some(Box::new(Cpu::from_difficulty(args.get(3).map_or("", |s| s.as_str())))),
+                    Some("tbp") if args.len() > 3 => {
+                        let bot = ExternalBot::spawn(&args[3..]).expect("Failed to start the bot");
+                        Some(Box::new(bot))
+                    }
+                    _ => None,
+                };
+                Game::Versus(VersusGame::new(), cpu)
+            }
+            _ => Game::Marathon(TetrisBoard::new()),
+        }
+    }
+
+    // For watching a replay or a simulated game.
+    pub fn from_ruleset(ruleset: Ruleset) -> Self {
+        match ruleset {
+            Ruleset::Marathon(board) => Game::Marathon(board),
+            Ruleset::Dig(dig_game) => Game::Dig(dig_game),
+            Ruleset::Master(master_game) => Game::Master(master_game),
+        }
+    }
+
+    pub fn update(&mut self, inputs: &[Input]) {
+        match *self {
+            Game::Marathon(ref mut board) => board.update(&inputs[0]),
+            Game::Dig(ref mut dig_game) => dig_game.update(&inputs[0]),
+            Game::Master(ref mut master_game) => master_game.update(&inputs[0]),
+            Game::Puzzle(ref mut puzzle_game) => puzzle_game.update(&inputs[0]),
+            Game::Pc(ref mut pc_game) => pc_game.update(&inputs[0]),
+            Game::Versus(ref mut versus_game, None) => versus_game.update(inputs),
+            Game::Versus(ref mut versus_game, Some(ref mut cpu)) => {
+                let cpu_input = cpu.next_input(&versus_game.players[1].board);
+                versus_game.update(&[inputs[0], cpu_input]);
+            }
+        }
+    }
+
+    // The board of single player modes.
+    pub fn board(&self) -> Option<&TetrisBoard> {
+        match *self {
+            Game::Marathon(ref board) => Some(board),
+            Game::Dig(ref dig_game) => Some(&dig_game.board),
+            Game::Master(ref master_game) => Some(&master_game.board),
+            Game::Puzzle(ref puzzle_game) => Some(&puzzle_game.board),
+            Game::Pc(ref pc_game) => Some(&pc_game.board),
+            Game::Versus(..) => None,
+        }
+    }
+
+    // From the beginning, or the same puzzle again.
+    pub fn restart(&mut self) {
+        match *self {
+            Game::Marathon(ref mut board) => board.reset(),
+            Game::Dig(ref mut dig_game) => dig_game.restart(),
+            Game::Master(ref mut master_game) => master_game.restart(),
+            Game::Puzzle(ref mut puzzle_game) => puzzle_game.retry(),
+            Game::Pc(ref mut pc_game) => pc_game.restart(),
+            Game::Versus(ref mut versus_game, _) => versus_game.restart(),
+        }
+    }
+
+    // The level, for modes that have them. Master's goes up a section at a
+    // time, counted in levels.
+    pub fn level(&self) -> Option<u32> {
+        match *self {
+            Game::Marathon(ref board) => Some(board.level),
+            Game::Dig(ref dig_game) => Some(dig_game.board.level),
+            Game::Master(ref master_game) => Some(master_game.level / 100 * 100),
+            _ => None,
+        }
+    }
+
+    // Whether a single player game has ended, by topping out or clearing it.
+    pub fn is_finished(&self) -> bool {
+        match *self {
+            Game::Marathon(ref board) => board.is_game_over,
+            Game::Dig(ref dig_game) => dig_game.board.is_game_over || dig_game.is_complete,
+            Game::Master(ref master_game) => master_game.board.is_game_over || master_game.is_complete,
+            Game::Puzzle(ref puzzle_game) => puzzle_game.is_solved || puzzle_game.is_failed,
+            Game::Pc(ref pc_game) => pc_game.board.is_game_over,
+            Game::Versus(..) => false,
+        }
+    }
+}
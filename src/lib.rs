@@ -0,0 +1,1016 @@
+extern crate gif;
+extern crate png;
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+
+pub mod ai;
+pub mod controls;
+pub mod cpu;
+pub mod dig;
+pub mod effects;
+pub mod export;
+pub mod finesse;
+pub mod frame;
+pub mod fumen;
+pub mod game;
+pub mod master;
+pub mod pc;
+pub mod pieces;
+pub mod puzzle;
+pub mod replay;
+pub mod rotation;
+pub mod sim;
+pub mod sound;
+pub mod tbp;
+pub mod theme;
+pub mod versus;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use std::cmp;
+
+use pieces::{piece_set, MAX_PIECE_CELLS};
+
+// Mirrors sdl2's Color, so the engine can be used without SDL.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    #[allow(non_snake_case)]
+    pub fn RGB(r: u8, g: u8, b: u8) -> Self {
+        Color {
+            r,
+            g,
+            b,
+        }
+    }
+}
+
+// Named before the naming rules for statics, and used everywhere since.
+#[allow(non_upper_case_globals)]
+pub static start_pos: Pos2D = Pos2D {
+    x: 5,
+    y: 2
+};
+#[allow(non_upper_case_globals)]
+pub static tetris_board_width : usize = 12;
+#[allow(non_upper_case_globals)]
+pub static tetris_board_height : usize = 24;
+
+#[derive(Copy, Clone)]
+pub struct Pos2D {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Pos2D {
+    pub fn xy(x:i32, y:i32) -> Self {
+        Pos2D {
+            x,
+            y,
+        }
+    }
+
+    pub fn add(&mut self, pos: Pos2D) {
+        self.x += pos.x;
+        self.y += pos.y;
+    }
+
+    pub fn inv(&self) -> Self {
+        Pos2D {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+// A piece, known by its letter in the piece set.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PieceKind(pub char);
+
+impl PieceKind {
+    pub const I: PieceKind = PieceKind('I');
+    pub const O: PieceKind = PieceKind('O');
+    pub const T: PieceKind = PieceKind('T');
+    pub const S: PieceKind = PieceKind('S');
+    pub const Z: PieceKind = PieceKind('Z');
+    pub const J: PieceKind = PieceKind('J');
+    pub const L: PieceKind = PieceKind('L');
+
+    pub fn letter(&self) -> char {
+        self.0
+    }
+
+    // Only letters of pieces in the set being played with.
+    pub fn from_letter(letter: &str) -> Option<PieceKind> {
+        let mut chars = letter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => piece_set().get(PieceKind(c)).map(|piece| piece.kind),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct TetrisPiece {
+    pub kind: PieceKind,
+    pub pos: Pos2D,
+    // Cells in each rotation state; only the first `size` count.
+    pub shape: [[Pos2D; MAX_PIECE_CELLS]; 4],
+    pub size: usize,
+    pub orientation: usize, 
+    // Drawing Related Constants
+    pub color: Color,
+}
+
+impl TetrisPiece {
+    pub fn build(kind: PieceKind, pos: Pos2D) -> Self {
+        let def = piece_set().get(kind).expect("No such piece in the piece set");
+        TetrisPiece {
+            kind: kind,
+            pos: pos,
+            shape: def.shape,
+            size: def.size,
+            color: def.color,
+            orientation: 0usize,
+        }
+    }
+
+    // The piece as it enters the board at `pos`.
+    pub fn spawn(kind: PieceKind, pos: Pos2D) -> Self {
+        let mut piece = TetrisPiece::build(kind, pos);
+        piece.move_by(piece_set().get(kind).unwrap().spawn);
+        piece
+    }
+
+    // Cells relative to the piece's position.
+    pub fn blocks(&self) -> &[Pos2D] {
+        &self.shape[self.orientation][..self.size]
+    }
+
+    pub fn move_by(&mut self, pos: Pos2D) {
+        self.pos.add(pos);
+    }
+
+    pub fn rotate_right(&mut self) {
+        self.orientation = (self.orientation + 1) % 4;
+    }
+    pub fn rotate_left(&mut self) {
+        self.orientation = (self.orientation + 3) % 4;
+    }
+
+    // Turned clockwise by the rotation system, if it can turn. `fits` says
+    // whether a piece is clear of the walls and the stack.
+    pub fn rotated_right<F: Fn(&TetrisPiece) -> bool>(&self, fits: F) -> Option<TetrisPiece> {
+        piece_set().rotation_system.rotate(self, true, &fits)
+    }
+
+    pub fn rotated_left<F: Fn(&TetrisPiece) -> bool>(&self, fits: F) -> Option<TetrisPiece> {
+        piece_set().rotation_system.rotate(self, false, &fits)
+    }
+
+    pub fn iter(&self) -> TetrisPieceIter<'_> {
+        TetrisPieceIter {
+            block_num: 0usize,
+            piece: self,
+        }
+    }
+}
+
+pub struct RandomTetrisPieceGenerator {
+    // Upcoming pieces, always at least a bag's worth so they can be previewed.
+    piece_seq: Vec<i32>,
+    // Set for a repeatable sequence of pieces, which starts over on reset.
+    seed: Option<usize>,
+    // Set when the upcoming pieces were given rather than dealt, so no more
+    // are dealt until they run out.
+    is_fixed: bool,
+    rng: StdRng,
+}
+
+impl Default for RandomTetrisPieceGenerator {
+    fn default() -> Self {
+        RandomTetrisPieceGenerator::new()
+    }
+}
+
+impl RandomTetrisPieceGenerator {
+    pub fn new() -> Self {
+        RandomTetrisPieceGenerator::build(None)
+    }
+
+    pub fn with_seed(seed: usize) -> Self {
+        RandomTetrisPieceGenerator::build(Some(seed))
+    }
+
+    fn build(seed: Option<usize>) -> Self {
+        let mut rng = RandomTetrisPieceGenerator::new_rng(seed);
+        RandomTetrisPieceGenerator {
+            piece_seq: RandomTetrisPieceGenerator::next_permut(&mut rng),
+            seed: seed,
+            is_fixed: false,
+            rng: rng,
+        }
+    }
+
+    // Deals the given pieces in order, then random ones.
+    pub fn with_sequence(kinds: &[PieceKind]) -> Self {
+        let mut tetris_gen = RandomTetrisPieceGenerator::new();
+        tetris_gen.piece_seq = kinds.iter()
+            .map(|&kind| piece_set().pieces.iter().position(|piece| piece.kind == kind).unwrap() as i32)
+            .collect();
+        tetris_gen.is_fixed = true;
+        tetris_gen
+    }
+
+    fn new_rng(seed: Option<usize>) -> StdRng {
+        match seed {
+            Some(seed) => StdRng::from_seed(&[seed][..]),
+            None => StdRng::new().unwrap(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.is_fixed = false;
+        if self.seed.is_some() {
+            self.rng = RandomTetrisPieceGenerator::new_rng(self.seed);
+        }
+        self.piece_seq = RandomTetrisPieceGenerator::next_permut(&mut self.rng);
+    }
+    
+    fn next_permut(rng: &mut StdRng) -> Vec<i32> {
+        // Generate a permutation of every tetris piece.
+        let mut piece_seq: Vec<i32> = (0..piece_set().pieces.len() as i32).collect();
+        {
+            let slice: &mut [i32] = &mut piece_seq;
+            rng.shuffle(slice);
+        }
+        piece_seq
+    }
+
+    pub fn get_next_piece(&mut self, pos: Pos2D) -> TetrisPiece {
+        if (self.piece_seq.len() <= piece_set().pieces.len() && !self.is_fixed) || self.piece_seq.is_empty() {
+            self.piece_seq.extend(RandomTetrisPieceGenerator::next_permut(&mut self.rng));
+        }
+        let num = self.piece_seq.remove(0);
+        self.get_piece_for_num(num, pos)
+    }
+
+    pub fn preview(&self, count: usize, pos: Pos2D) -> Vec<TetrisPiece> {
+        self.piece_seq.iter()
+            .take(count)
+            .map(|&num| self.get_piece_for_num(num, pos))
+            .collect()
+    }
+
+    fn get_piece_for_num(&self, num: i32, pos: Pos2D) -> TetrisPiece {
+        TetrisPiece::spawn(piece_set().pieces[num as usize].kind, pos)
+    }
+}
+
+pub struct TetrisPieceIter<'a> {
+    block_num: usize,
+    piece: &'a TetrisPiece,
+}
+
+impl<'a> Iterator for TetrisPieceIter<'a> {
+    type Item = Pos2D;
+
+    fn next(&mut self) -> Option<Pos2D> {
+        if self.block_num == self.piece.size {
+            return None;
+        }
+        let diff = &self.piece.shape[self.piece.orientation][self.block_num];
+        let mut pos = Pos2D::xy(self.piece.pos.x, self.piece.pos.y);
+
+        pos.x += diff.x;
+        pos.y += diff.y;
+
+        self.block_num += 1;
+
+        Some(pos)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Input {
+    pub left_key_pressed: bool,
+    pub right_key_pressed: bool,
+    pub up_key_pressed: bool,
+    pub down_key_pressed: bool,
+    pub hard_drop_key_pressed: bool,
+    pub hold_key_pressed: bool,
+    // Up rotates clockwise; these turn the other way, and halfway round.
+    pub rotate_left_key_pressed: bool,
+    pub rotate_180_key_pressed: bool,
+    // Set for each key pressed this frame that is only being held down, so
+    // a held key counts as one input for finesse.
+    pub left_is_repeat: bool,
+    pub right_is_repeat: bool,
+    pub up_is_repeat: bool,
+    pub rotate_left_is_repeat: bool,
+    pub rotate_180_is_repeat: bool,
+}
+
+// Anything that plays a board by pressing the same keys a human would.
+pub trait Controller {
+    fn name(&self) -> &str;
+    fn next_input(&mut self, board: &TetrisBoard) -> Input;
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input::new()
+    }
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Input {
+            left_key_pressed: false,
+            right_key_pressed: false,
+            up_key_pressed: false,
+            down_key_pressed: false,
+            hard_drop_key_pressed: false,
+            hold_key_pressed: false,
+            rotate_left_key_pressed: false,
+            rotate_180_key_pressed: false,
+            left_is_repeat: false,
+            right_is_repeat: false,
+            up_is_repeat: false,
+            rotate_left_is_repeat: false,
+            rotate_180_is_repeat: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.left_key_pressed = false;
+        self.right_key_pressed = false;
+        self.up_key_pressed = false;
+        self.down_key_pressed = false;
+        self.hard_drop_key_pressed = false;
+        self.hold_key_pressed = false;
+        self.rotate_left_key_pressed = false;
+        self.rotate_180_key_pressed = false;
+        self.left_is_repeat = false;
+        self.right_is_repeat = false;
+        self.up_is_repeat = false;
+        self.rotate_left_is_repeat = false;
+        self.rotate_180_is_repeat = false;
+    }
+
+    // One bit per key, for storing replays compactly.
+    pub fn to_bits(&self) -> u16 {
+        (self.left_key_pressed as u16) |
+            (self.right_key_pressed as u16) << 1 |
+            (self.up_key_pressed as u16) << 2 |
+            (self.down_key_pressed as u16) << 3 |
+            (self.hard_drop_key_pressed as u16) << 4 |
+            (self.left_is_repeat as u16) << 5 |
+            (self.hold_key_pressed as u16) << 6 |
+            (self.rotate_left_key_pressed as u16) << 7 |
+            (self.rotate_180_key_pressed as u16) << 8 |
+            (self.right_is_repeat as u16) << 9 |
+            (self.up_is_repeat as u16) << 10 |
+            (self.rotate_left_is_repeat as u16) << 11 |
+            (self.rotate_180_is_repeat as u16) << 12
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        Input {
+            left_key_pressed: bits & 1 != 0,
+            right_key_pressed: bits & 1 << 1 != 0,
+            up_key_pressed: bits & 1 << 2 != 0,
+            down_key_pressed: bits & 1 << 3 != 0,
+            hard_drop_key_pressed: bits & 1 << 4 != 0,
+            left_is_repeat: bits & 1 << 5 != 0,
+            hold_key_pressed: bits & 1 << 6 != 0,
+            rotate_left_key_pressed: bits & 1 << 7 != 0,
+            rotate_180_key_pressed: bits & 1 << 8 != 0,
+            right_is_repeat: bits & 1 << 9 != 0,
+            up_is_repeat: bits & 1 << 10 != 0,
+            rotate_left_is_repeat: bits & 1 << 11 != 0,
+            rotate_180_is_repeat: bits & 1 << 12 != 0,
+        }
+    }
+}
+
+// Timings for modes with their own speed curve. Gravity is in 1/256ths of a
+// row per frame, so 256 is 1G and 5120 is 20G.
+#[derive(Copy, Clone)]
+pub struct Speed {
+    pub gravity: u32,
+    pub are: u32,
+    pub line_clear_delay: u32,
+    pub lock_delay: u32,
+}
+
+// Points for clearing 0 to 4 lines, or 5 with pentominoes, normally and with
+// a T-spin, times the level.
+static LINE_SCORES: [u32; 6] = [0, 100, 300, 500, 800, 1200];
+static T_SPIN_LINE_SCORES: [u32; 4] = [400, 800, 1200, 1600];
+
+// How the game ended.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TopOut {
+    // The next piece had nowhere to spawn.
+    BlockOut,
+    // Garbage pushed the stack through the ceiling.
+    GarbageOut,
+}
+
+impl TopOut {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TopOut::BlockOut => "block out",
+            TopOut::GarbageOut => "garbage out",
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct TetrisUnitBlock {
+    pub is_filled: bool,
+    pub color: Color,
+    pub is_garbage: bool,
+    // The piece it was part of, if any.
+    pub kind: Option<PieceKind>,
+}
+
+pub struct TetrisBoard {
+    pub width: usize,
+    pub height: usize,
+    pub board: Vec<Vec<TetrisUnitBlock>>,
+    // Where pieces enter, at the top in the middle.
+    pub spawn_pos: Pos2D,
+    // Blocks drawn per cell each way, 2 in big mode.
+    pub block_scale: u32,
+    pub active_piece: TetrisPiece,
+    pub tetris_gen: RandomTetrisPieceGenerator,
+    pub hold_piece: Option<PieceKind>,
+    // Only one hold per piece, until it locks.
+    pub is_hold_used: bool,
+    gravity: u32,
+    gravity_countdown: u32,
+    // Rows the piece falls each time the gravity countdown runs out.
+    gravity_rows: u32,
+    lock_delay: u32,
+    lock_delay_countdown: u32,
+    // Entry delay, between a piece locking and the next one spawning.
+    are: u32,
+    are_countdown: u32,
+    line_clear_delay: u32,
+    line_clear_countdown: u32,
+    // Set by modes with their own speed curve, otherwise gravity follows the level.
+    speed: Option<Speed>,
+    pub lines_cleared: u32,
+    pub last_clear_lines: u32,
+    pub last_lock_was_t_spin: bool,
+    last_move_was_rotation: bool,
+    pub pieces_locked: u32,
+    pub last_locked_piece: Option<TetrisPiece>,
+    pub score: u32,
+    pub is_game_over: bool,
+    pub top_out: Option<TopOut>,
+    game_over_delay: u32,
+    game_over_countdown: u32,
+    locking_state: bool,
+    pub level: u32,
+}
+
+
+impl Default for TetrisBoard {
+    fn default() -> Self {
+        TetrisBoard::new()
+    }
+}
+
+impl TetrisBoard {
+
+    pub fn new() -> Self {
+        TetrisBoard::with_size(tetris_board_width, tetris_board_height)
+    }
+
+    // Big mode: every cell is a 2x2 block, so the field is half as wide and
+    // half as tall.
+    pub fn big() -> Self {
+        let mut board = TetrisBoard::with_size((tetris_board_width - 2) / 2 + 2, (tetris_board_height - 2) / 2 + 2);
+        board.block_scale = 2;
+        board
+    }
+
+    // A board of the given size, walls included.
+    fn with_size(width: usize, height: usize) -> Self {
+
+        let mut board: Vec<Vec<TetrisUnitBlock>> = Vec::new();
+        let spawn_pos = Pos2D::xy((width as i32 - 1) / 2, start_pos.y);
+
+        for _ in 0usize..height {
+            board.push(vec![TetrisUnitBlock { is_filled: false, color: Color::RGB(0,0,0), is_garbage: false, kind: None }; width]);
+        }
+
+        let wall = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255), is_garbage: false, kind: None };
+        board[0] = vec![wall; width];
+        board[height-1] = vec![wall; width];
+        for row in board.iter_mut() {
+            row[0] = wall;
+            row[width-1] = wall;
+        }
+
+        let mut tetris_gen = RandomTetrisPieceGenerator::new();
+
+        TetrisBoard {
+            width: width,
+            height: height,
+            board: board,
+            spawn_pos: spawn_pos,
+            block_scale: 1,
+            active_piece: tetris_gen.get_next_piece(spawn_pos),
+            tetris_gen: tetris_gen,
+            hold_piece: None,
+            is_hold_used: false,
+            gravity: 20,
+            gravity_countdown:  20,
+            gravity_rows: 1,
+            lock_delay: 30,
+            lock_delay_countdown: 30,
+            are: 0,
+            are_countdown: 0,
+            line_clear_delay: 0,
+            line_clear_countdown: 0,
+            speed: None,
+            lines_cleared: 0,
+            last_clear_lines: 0,
+            last_lock_was_t_spin: false,
+            last_move_was_rotation: false,
+            pieces_locked: 0,
+            last_locked_piece: None,
+            score: 0,
+            is_game_over: false,
+            top_out: None,
+            game_over_delay: 60,
+            game_over_countdown: 0,
+            locking_state: false,
+            level: 1,
+        }
+    }
+
+    // A board that always deals the same pieces for the same seed.
+    pub fn with_seed(seed: usize) -> Self {
+        let mut board = TetrisBoard::new();
+        board.tetris_gen = RandomTetrisPieceGenerator::with_seed(seed);
+        board.active_piece = board.tetris_gen.get_next_piece(board.spawn_pos);
+        board
+    }
+
+    // A board that starts from a given stack and pieces instead of an empty
+    // one. The stack's rows are given top to bottom and sit on the floor.
+    pub fn with_setup(stack: &[Vec<TetrisUnitBlock>], tetris_gen: RandomTetrisPieceGenerator) -> Self {
+        let mut board = TetrisBoard::new();
+        let top = board.height - 1 - cmp::min(stack.len(), board.height - 2);
+        for (row, blocks) in stack[stack.len() - (board.height - 1 - top)..].iter().enumerate() {
+            for (x, &block) in blocks.iter().take(board.width - 2).enumerate() {
+                board.board[top + row][x + 1] = block;
+            }
+        }
+        board.tetris_gen = tetris_gen;
+        board.active_piece = board.tetris_gen.get_next_piece(board.spawn_pos);
+        if !board.is_valid() {
+            board.is_game_over = true;
+            board.top_out = Some(TopOut::BlockOut);
+        }
+        board
+    }
+
+    fn is_valid(&self) -> bool {
+        self.fits(&self.active_piece)
+    }
+
+    fn fits(&self, piece: &TetrisPiece) -> bool {
+        piece.iter().all(|pos| {
+            pos.x >= 0 && pos.y >= 0 &&
+                (pos.x as usize) < self.width && (pos.y as usize) < self.height &&
+                !self.board[pos.y as usize][pos.x as usize].is_filled
+        })
+    }
+
+    fn move_active_piece(&mut self, pos: Pos2D) -> bool {
+        self.active_piece.move_by(pos);
+
+        if !self.is_valid() {
+            self.active_piece.move_by(pos.inv());
+            false
+        } else {
+            self.last_move_was_rotation = false;
+            true
+        }
+    }
+
+    fn rotate_active_piece_to(&mut self, rotated: Option<TetrisPiece>) -> bool {
+        match rotated {
+            Some(rotated) => {
+                self.active_piece = rotated;
+                self.last_move_was_rotation = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn rotate_active_piece_right(&mut self) -> bool {
+        let rotated = self.active_piece.rotated_right(|piece| self.fits(piece));
+        self.rotate_active_piece_to(rotated)
+    }
+
+    fn rotate_active_piece_left(&mut self) -> bool {
+        let rotated = self.active_piece.rotated_left(|piece| self.fits(piece));
+        self.rotate_active_piece_to(rotated)
+    }
+
+    // Two clockwise turns, each kicked, as one move.
+    fn rotate_active_piece_180(&mut self) -> bool {
+        let rotated = self.active_piece.rotated_right(|piece| self.fits(piece))
+            .and_then(|half| half.rotated_right(|piece| self.fits(piece)));
+        self.rotate_active_piece_to(rotated)
+    }
+
+    // Three corner rule: a T rotated into place with at least three of the
+    // four cells diagonal to its center filled.
+    fn is_t_spin(&self) -> bool {
+        if self.active_piece.kind != PieceKind::T || !self.last_move_was_rotation {
+            return false;
+        }
+        let center = self.active_piece.pos;
+        let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+        let filled = corners.iter()
+            .filter(|&&(x, y)| self.board[(center.y + y) as usize][(center.x + x) as usize].is_filled)
+            .count();
+        filled >= 3
+    }
+
+    fn consume(&mut self, piece: TetrisPiece) {
+        for pos in piece.iter() {
+            self.board[pos.y as usize][pos.x as usize].is_filled = true;
+            self.board[pos.y as usize][pos.x as usize].color = piece.color;
+            self.board[pos.y as usize][pos.x as usize].kind = Some(piece.kind);
+        }
+    }
+
+    fn is_row_full(&self, row: usize) -> bool {
+        for i in 1..self.width-1 {
+            if !self.board[row][i].is_filled {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn is_row_empty(&self, row:usize) -> bool {
+        for i in 1..self.width-1 {
+            if self.board[row][i].is_filled {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn count_full_rows(&self) -> u32 {
+        (1..self.height-1).filter(|&i| self.is_row_full(i)).count() as u32
+    }
+
+    // Full rows count as cleared, so this also holds during the line clear delay.
+    pub fn is_perfect_clear(&self) -> bool {
+        (1..self.height-1).all(|i| self.is_row_full(i) || self.is_row_empty(i))
+    }
+
+    fn shift_down(&mut self, row:usize) {
+        for i in (2..row+1).rev() {
+            for j in 1..self.width-1 {
+                self.board[i][j] = self.board[i-1][j];
+            }
+        }
+    }
+
+    pub fn field_width(&self) -> usize {
+        self.width - 2
+    }
+
+    // Where a column starts when drawn, in blocks, and how many blocks wide
+    // it is. The walls stay one block thick in big mode, so every board
+    // takes up the same room.
+    pub fn column_span(&self, x: usize) -> (i32, i32) {
+        TetrisBoard::block_span(x, self.width, self.block_scale as i32)
+    }
+
+    pub fn row_span(&self, y: usize) -> (i32, i32) {
+        TetrisBoard::block_span(y, self.height, self.block_scale as i32)
+    }
+
+    fn block_span(i: usize, len: usize, scale: i32) -> (i32, i32) {
+        match i {
+            0 => (0, 1),
+            _ if i == len - 1 => (1 + (i as i32 - 1) * scale, 1),
+            _ => (1 + (i as i32 - 1) * scale, scale),
+        }
+    }
+
+    // Pushes the stack up by one row and fills the bottom row with garbage,
+    // leaving a hole at the given column (counted from the left wall).
+    // Returns false if this tops out the player.
+    pub fn insert_garbage_row(&mut self, hole: usize) -> bool {
+        // Anything in the top row gets pushed into the ceiling.
+        let mut topped_out = !self.is_row_empty(1);
+
+        for i in 1..self.height-2 {
+            for j in 1..self.width-1 {
+                self.board[i][j] = self.board[i+1][j];
+            }
+        }
+
+        let row = self.height - 2;
+        for j in 1..self.width-1 {
+            let is_filled = j != hole + 1;
+            self.board[row][j] = TetrisUnitBlock {
+                is_filled: is_filled,
+                color: if is_filled { Color::RGB(128,128,128) } else { Color::RGB(0,0,0) },
+                is_garbage: is_filled,
+                kind: None,
+            };
+        }
+
+        // The active piece rides up with the stack if it is in the way.
+        if !self.is_valid() && !self.move_active_piece(Pos2D::xy(0, -1)) {
+            topped_out = true;
+        }
+
+        if topped_out {
+            self.is_game_over = true;
+            self.top_out = Some(TopOut::GarbageOut);
+            self.game_over_countdown = self.game_over_delay;
+        }
+        !topped_out
+    }
+
+    pub fn garbage_rows(&self) -> u32 {
+        let mut rows = 0;
+        for i in 1..self.height-1 {
+            if (1..self.width-1).any(|j| self.board[i][j].is_garbage) {
+                rows += 1;
+            }
+        }
+        rows
+    }
+
+    fn clear_lines(&mut self) {
+        for i in (1..self.height-1).rev() {
+            while self.is_row_full(i) {
+                self.lines_cleared += 1;
+                self.shift_down(i);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for i in 0usize..self.width {
+            for j in 0usize ..self.height {
+                self.board[j][i] = TetrisUnitBlock {is_filled: false, color: Color::RGB(0,0,0), is_garbage: false, kind: None};
+            }
+        }
+        for i in 0usize..self.width {
+            self.board[0][i] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255), is_garbage: false, kind: None };
+            self.board[self.height-1][i] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255), is_garbage: false, kind: None };
+        }
+        for i in 0usize..self.height {
+            self.board[i][0] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255), is_garbage: false, kind: None };
+            self.board[i][self.width-1] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255), is_garbage: false, kind: None };
+        }
+
+        self.tetris_gen.reset();
+        self.active_piece = self.tetris_gen.get_next_piece(self.spawn_pos);
+        self.hold_piece= None;
+        self.is_hold_used= false;
+        self.gravity= 20;
+        self.gravity_countdown=  20;
+        self.gravity_rows= 1;
+        self.lock_delay= 30;
+        self.lock_delay_countdown= 30;
+        self.are= 0;
+        self.are_countdown= 0;
+        self.line_clear_delay= 0;
+        self.line_clear_countdown= 0;
+        self.speed= None;
+        self.lines_cleared= 0;
+        self.last_clear_lines= 0;
+        self.last_lock_was_t_spin= false;
+        self.last_move_was_rotation= false;
+        self.pieces_locked= 0;
+        self.last_locked_piece= None;
+        self.score= 0;
+        self.is_game_over= false;
+        self.top_out= None;
+        self.game_over_delay= 60;
+        self.game_over_countdown= 0;
+        self.locking_state = false;
+        self.level = 1;
+
+    }
+
+
+
+    pub fn is_piece_active(&self) -> bool {
+        self.line_clear_countdown == 0 && self.are_countdown == 0
+    }
+
+    fn spawn_next_piece(&mut self) {
+        let piece = self.tetris_gen.get_next_piece(self.spawn_pos);
+        self.spawn(piece);
+    }
+
+    fn spawn(&mut self, piece: TetrisPiece) {
+        self.active_piece = piece;
+        if !self.is_valid() {
+            self.is_game_over = true;
+            self.top_out = Some(TopOut::BlockOut);
+            self.game_over_countdown = self.game_over_delay;
+        }
+    }
+
+    // Swaps the active piece with the held one, or the next one if nothing
+    // is held yet. The swapped in piece starts again from the top.
+    fn hold_active_piece(&mut self) {
+        let held = self.active_piece.kind;
+        match self.hold_piece {
+            Some(kind) => self.spawn(TetrisPiece::spawn(kind, self.spawn_pos)),
+            None => self.spawn_next_piece(),
+        }
+        self.hold_piece = Some(held);
+        self.is_hold_used = true;
+        self.gravity_countdown = self.gravity;
+        self.lock_delay_countdown = self.lock_delay;
+        self.locking_state = false;
+        self.last_move_was_rotation = false;
+    }
+
+    fn lock_active_piece(&mut self) {
+        let piece_to_consume = self.active_piece;
+        self.last_lock_was_t_spin = self.is_t_spin();
+        self.consume(piece_to_consume);
+        self.pieces_locked += 1;
+        self.last_locked_piece = Some(piece_to_consume);
+        self.is_hold_used = false;
+
+        self.last_clear_lines = self.count_full_rows();
+        let line_scores: &[u32] = if self.last_lock_was_t_spin { &T_SPIN_LINE_SCORES } else { &LINE_SCORES };
+        // Bigger pieces can clear more lines, which score as the most there is.
+        let lines = cmp::min(self.last_clear_lines as usize, line_scores.len() - 1);
+        self.score += line_scores[lines] * self.level;
+        if self.last_clear_lines > 0 && self.line_clear_delay > 0 {
+            self.line_clear_countdown = self.line_clear_delay;
+        } else {
+            self.clear_lines();
+        }
+        self.are_countdown = self.are;
+
+        self.lock_delay_countdown = self.lock_delay;
+        self.locking_state = false;
+
+        if self.is_piece_active() {
+            self.spawn_next_piece();
+        }
+    }
+
+    fn apply_speed(&mut self, soft_drop: bool) {
+        match self.speed {
+            Some(speed) => {
+                // Soft drop is 1G, so it only speeds up slower gravity.
+                let gravity = if soft_drop { cmp::max(speed.gravity, 256) } else { cmp::max(speed.gravity, 1) };
+                if gravity >= 256 {
+                    self.gravity = 1;
+                    self.gravity_rows = gravity / 256;
+                } else {
+                    self.gravity = 256 / gravity;
+                    self.gravity_rows = 1;
+                }
+                self.are = speed.are;
+                self.line_clear_delay = speed.line_clear_delay;
+                self.lock_delay = speed.lock_delay;
+            }
+            None => {
+                if soft_drop {
+                    self.gravity = 2;
+                } else {
+                    self.gravity = 3 * (11 - self.level);
+                }
+                self.gravity_rows = 1;
+            }
+        }
+    }
+
+    pub fn update(&mut self, input: &Input) {
+
+        if self.is_game_over {
+            if self.game_over_countdown > 0 {
+                self.game_over_countdown -= 1;
+            }
+            if self.game_over_countdown == 0 && input.up_key_pressed {
+                self.reset();
+            }
+            return;
+        }
+
+        self.apply_speed(input.down_key_pressed);
+
+        // Wait out the line clear delay and ARE before the next piece.
+        if !self.is_piece_active() {
+            if self.line_clear_countdown > 0 {
+                self.line_clear_countdown -= 1;
+                if self.line_clear_countdown == 0 {
+                    self.clear_lines();
+                }
+            } else {
+                self.are_countdown -= 1;
+            }
+            if self.is_piece_active() {
+                self.spawn_next_piece();
+            }
+            return;
+        }
+
+        // Handle Input
+        if input.hold_key_pressed && !self.is_hold_used {
+            self.hold_active_piece();
+            if self.is_game_over {
+                return;
+            }
+        }
+        if input.left_key_pressed {
+            self.move_active_piece(Pos2D::xy(-1, 0));
+        } 
+        if input.right_key_pressed {
+            self.move_active_piece(Pos2D::xy(1, 0));
+        }
+        if input.up_key_pressed {
+            self.rotate_active_piece_right();
+        }
+        if input.rotate_left_key_pressed {
+            self.rotate_active_piece_left();
+        }
+        if input.rotate_180_key_pressed {
+            self.rotate_active_piece_180();
+        }
+        if input.hard_drop_key_pressed {
+            while self.move_active_piece(Pos2D::xy(0,1)) {}
+            // Lock straight away.
+            self.locking_state = true;
+            self.lock_delay_countdown = 0;
+        }
+
+        // Countdown the timers.
+        if self.gravity_countdown > 0 {
+            self.gravity_countdown -= 1;
+        }
+        if self.lock_delay_countdown > 0 && self.locking_state {
+            self.lock_delay_countdown -= 1;
+        }
+
+
+        // Move piece down if gravity countdown is done.
+        if self.gravity_countdown == 0 || self.locking_state {
+            self.gravity_countdown = self.gravity;
+            let mut move_down_success = false;
+            for _ in 0..self.gravity_rows {
+                if !self.move_active_piece(Pos2D::xy(0,1)) {
+                    break;
+                }
+                move_down_success = true;
+            }
+
+            // Reset lock delay if piece moved down.
+            if move_down_success {
+                self.lock_delay_countdown = self.lock_delay;
+                self.game_over_countdown = self.game_over_delay;
+                self.locking_state = false;
+            } else {
+                self.locking_state = true;
+            }
+        }
+
+        if self.locking_state && self.lock_delay_countdown == 0 {
+            self.lock_active_piece();
+        }
+
+        self.level = (self.lines_cleared / 10) + 1;
+        if self.level >= 10 {
+            self.level = 10;
+        }
+
+    }
+}
@@ -1,681 +1,820 @@
 extern crate sdl2;
-extern crate rand;
-
-
-use rand::{thread_rng,Rng};
+extern crate rust_tetris;
 
 use sdl2::event::Event;
+use sdl2::image::{LoadTexture, SaveSurface};
 use sdl2::keyboard::{Keycode, Scancode};
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, RenderTarget, TextureCreator};
+use sdl2::mixer::{self, Channel, Chunk, LoaderRWops};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, RenderTarget, Texture, TextureCreator};
 use sdl2::render::TextureQuery;
+use sdl2::rwops::RWops;
+use sdl2::surface::{Surface, SurfaceContext};
+use sdl2::video::FullscreenType;
 use sdl2::ttf::Font;
 
-use std::mem;
+use rust_tetris::{Controller, Input, PieceKind, Pos2D};
+use rust_tetris::controls::{self, Action, ActionKeys, Controls, ControlsMenu, ACTIONS};
+use rust_tetris::effects::{EffectSettings, Effects};
+use rust_tetris::export::AnimationWriter;
+use rust_tetris::finesse::FinesseTracker;
+use rust_tetris::frame::{Block, Frame, TextSize, Tile, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rust_tetris::fumen::{self, Page};
+use rust_tetris::game::{self, Game};
+use rust_tetris::replay::{Replay, ReplayPlayer};
+use rust_tetris::sim::Ruleset;
+use rust_tetris::sound::{self, Sound, SoundTracker, Volume};
+use rust_tetris::theme::{self, Glyph, Glyphs, Pattern, Theme};
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::env;
+use std::cmp;
 use std::path::Path;
-use std::thread;
-use std::time::{Duration, Instant};
-
-static start_pos: Pos2D = Pos2D {
-    x: 5,
-    y: 2
-};
-static tetris_board_width : usize = 12;
-static tetris_board_height : usize = 24;
-
-#[derive(Copy, Clone)]
-struct Pos2D {
-    x: i32,
-    y: i32,
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+fn sdl_color(color: rust_tetris::Color) -> Color {
+    Color::RGB(color.r, color.g, color.b)
 }
 
-impl Pos2D {
-    fn xy(x:i32, y:i32) -> Self {
-        Pos2D {
-            x,
-            y,
+// Where the screen goes in the window: blocks as big as fit, in whole pixels
+// so they stay sharp, with the rest of the window left as a border.
+struct Layout {
+    box_width: i32,
+    offset: Pos2D,
+}
+
+impl Layout {
+    fn fit(width: u32, height: u32) -> Self {
+        let box_width = cmp::max(cmp::min(width as i32 / SCREEN_WIDTH, height as i32 / SCREEN_HEIGHT), 1);
+        Layout {
+            box_width: box_width,
+            offset: Pos2D::xy((width as i32 - box_width * SCREEN_WIDTH) / 2, (height as i32 - box_width * SCREEN_HEIGHT) / 2),
         }
     }
 
-    fn add(&mut self, pos: Pos2D) {
-        self.x = self.x + pos.x;
-        self.y = self.y + pos.y;
+    // The top left pixel of a block.
+    fn pixel(&self, x: i32, y: i32) -> Pos2D {
+        Pos2D::xy(self.offset.x + x * self.box_width, self.offset.y + y * self.box_width)
     }
 
-    fn inv(&self) -> Self {
-        Pos2D {
-            x: -self.x,
-            y: -self.y,
-        }
+    // 28 points with 20 pixel blocks.
+    fn font_size(&self) -> u16 {
+        (self.box_width * 7 / 5) as u16
     }
 }
 
-
-trait Drawable {
-    fn draw<T:RenderTarget>(&self, canvas: &mut Canvas<T>, pos:Pos2D);
+// A theme with its images loaded, for drawing on one canvas.
+struct Skin<'a> {
+    theme: &'a Theme,
+    tiles: HashMap<String, Texture<'a>>,
+    background: Option<Texture<'a>>,
+    // Glyph letters by letter, whether they are black rather than white, and
+    // font height.
+    letters: HashMap<(char, bool, i32), Texture<'a>>,
 }
 
-
-struct TetrisPiece {
-    pos: Pos2D,
-    shape: [[Pos2D; 4]; 4],
-    orientation: usize, 
-    // Drawing Related Constants
-    color: Color,
-}
-
-
-impl TetrisPiece {
-    fn build_i_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape: [
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(2,0) ],
-                [ Pos2D::xy(1,-1), Pos2D::xy(1,0), Pos2D::xy(1,1), Pos2D::xy(1,2) ],
-                [ Pos2D::xy(-1,1), Pos2D::xy(0,1), Pos2D::xy(1,1), Pos2D::xy(2,1) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(0,2) ],
-            ],
-            color: Color::RGB(0, 255, 255),
-            orientation: 0usize,
-        }
-    }
-
-    fn build_o_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape: [
-                [ Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,1), Pos2D::xy(0,1) ],
-                [ Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,1), Pos2D::xy(0,1) ],
-                [ Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,1), Pos2D::xy(0,1) ],
-                [ Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,1), Pos2D::xy(0,1) ],
-            ],
-            color: Color::RGB(255, 255, 0),
-            orientation: 0usize,
-        }
-    }
-
-    fn build_t_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape: [
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(-1,0) ],
-                [ Pos2D::xy(1,0), Pos2D::xy(0,1), Pos2D::xy(0,-1), Pos2D::xy(0,0) ],
-                [ Pos2D::xy(0,1), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(-1,0) ],
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,1), Pos2D::xy(0,-1), Pos2D::xy(0,0) ],
-            ],
-            color: Color::RGB(128, 0, 128),
-            orientation: 0usize,
-        }
+impl<'a> Skin<'a> {
+    fn load<F>(theme: &'a Theme, texture_creator: &'a TextureCreator<F>) -> Result<Self, String> {
+        let load = |path: &String| texture_creator.load_texture(path).map_err(|e| format!("Failed to load {}: {}", path, e));
+        let mut tiles = HashMap::new();
+        for (name, path) in theme.tiles.iter() {
+            tiles.insert(name.clone(), load(path)?);
+        }
+        let background = match theme.background {
+            Some(ref path) => Some(load(path)?),
+            None => None,
+        };
+        Ok(Skin {
+            theme: theme,
+            tiles: tiles,
+            background: background,
+            letters: HashMap::new(),
+        })
     }
+}
 
-    fn build_s_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape: [
-                [ Pos2D::xy(1,-1), Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(-1,0) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,1) ],
-                [ Pos2D::xy(1,0), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(-1,1) ],
-                [ Pos2D::xy(-1,-1), Pos2D::xy(0,0), Pos2D::xy(-1,0), Pos2D::xy(0,1) ],
-            ],
-
-            color: Color::RGB(0, 255, 0),
-            orientation: 0usize,
-        }
+// `font` is for the glyphs' letters.
+fn draw_block<'a, T:RenderTarget, F>(
+    canvas: &mut Canvas<T>,
+    texture_creator: &'a TextureCreator<F>,
+    font: &Font,
+    layout: &Layout,
+    skin: &mut Skin<'a>,
+    at: Pos2D,
+    block: &Block,
+    ) {
+    let box_width = layout.box_width;
+    let gap = cmp::max(box_width / 20, 1);
+    let pos = layout.pixel(at.x, at.y);
+    let color = skin.theme.color(block);
+    let rect = Rect::new(
+        pos.x + gap,
+        pos.y + gap,
+        (box_width - 2 * gap) as u32,
+        (box_width - 2 * gap) as u32
+    );
+    // Ghosts are only outlined.
+    if block.is_ghost {
+        canvas.set_draw_color(sdl_color(color));
+        canvas.draw_rect(rect).unwrap();
+        return;
+    }
+
+    // Tiles cover the whole block, borders and all. A piece's own tile is
+    // drawn as it is, the one for every block in the block's color.
+    let tile_rect = Rect::new(pos.x, pos.y, box_width as u32, box_width as u32);
+    if let Some(texture) = skin.tiles.get(&block.tile.name()) {
+        canvas.copy(texture, None, tile_rect).unwrap();
+    } else if let Some(texture) = skin.tiles.get_mut("block") {
+        texture.set_color_mod(color.r, color.g, color.b);
+        canvas.copy(texture, None, tile_rect).unwrap();
+    } else {
+        canvas.set_draw_color(sdl_color(color));
+        canvas.fill_rect(rect).unwrap();
+    }
+
+    if let Tile::Piece(kind) = block.tile {
+        draw_glyph(canvas, texture_creator, font, skin, rect, kind, color);
     }
+}
 
-    fn build_z_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape: [ 
-                [ Pos2D::xy(-1,-1), Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(1,0) ],
-                [ Pos2D::xy(1,-1), Pos2D::xy(1,0), Pos2D::xy(0,0), Pos2D::xy(0,1) ],
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(1,1) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(-1,0), Pos2D::xy(-1,1) ],
-                ],
-            color: Color::RGB(255, 0, 0),
-            orientation: 0usize,
+// Marks a piece's block, so it can be told apart without its color.
+fn draw_glyph<'a, T:RenderTarget, F>(
+    canvas: &mut Canvas<T>,
+    texture_creator: &'a TextureCreator<F>,
+    font: &Font,
+    skin: &mut Skin<'a>,
+    rect: Rect,
+    kind: PieceKind,
+    color: rust_tetris::Color,
+    ) {
+    let glyph_color = theme::glyph_color(color);
+    canvas.set_draw_color(sdl_color(glyph_color));
+    let size = rect.width() as i32;
+    let thickness = cmp::max(size / 8, 1);
+    // Patterns keep a quarter of the block clear around them.
+    let (left, top, inner) = (rect.x() + size / 4, rect.y() + size / 4, size - size / 4 * 2);
+    let (middle_x, middle_y) = (rect.x() + (size - thickness) / 2, rect.y() + (size - thickness) / 2);
+    let horizontal = Rect::new(left, middle_y, inner as u32, thickness as u32);
+    let vertical = Rect::new(middle_x, top, thickness as u32, inner as u32);
+    match skin.theme.glyph(kind) {
+        None => {}
+        Some(Glyph::Pattern(Pattern::HorizontalLine)) => canvas.fill_rect(horizontal).unwrap(),
+        Some(Glyph::Pattern(Pattern::VerticalLine)) => canvas.fill_rect(vertical).unwrap(),
+        Some(Glyph::Pattern(Pattern::Cross)) => {
+            canvas.fill_rect(horizontal).unwrap();
+            canvas.fill_rect(vertical).unwrap();
+        }
+        Some(Glyph::Pattern(Pattern::Dot)) => {
+            let dot = cmp::max(size / 3, 1);
+            canvas.fill_rect(Rect::new(rect.x() + (size - dot) / 2, rect.y() + (size - dot) / 2, dot as u32, dot as u32)).unwrap();
+        }
+        Some(Glyph::Pattern(Pattern::Square)) => {
+            for i in 0..thickness {
+                canvas.draw_rect(Rect::new(left + i, top + i, (inner - 2 * i) as u32, (inner - 2 * i) as u32)).unwrap();
+            }
         }
-    }
-
-    fn build_j_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape:[ 
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(-1,-1) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(1,-1) ],
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,1) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(-1,1) ],
-            ],
-            color: Color::RGB(0, 0, 255),
-            orientation: 0usize,
+        Some(Glyph::Pattern(pattern)) => {
+            // The two diagonals.
+            let is_rising = pattern == Pattern::Rising;
+            for i in 0..thickness {
+                let (start_y, end_y) = if is_rising { (top + inner - 1, top) } else { (top, top + inner - 1) };
+                canvas.draw_line(Point::new(left + i, start_y), Point::new(left + inner - 1 + i, end_y)).unwrap();
+            }
         }
-    }
-
-    fn build_l_piece(pos: Pos2D) -> Self {
-        TetrisPiece {
-            pos: pos,
-            shape: [
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(1,-1) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(1,1) ],
-                [ Pos2D::xy(-1,0), Pos2D::xy(0,0), Pos2D::xy(1,0), Pos2D::xy(-1,1) ],
-                [ Pos2D::xy(0,-1), Pos2D::xy(0,0), Pos2D::xy(0,1), Pos2D::xy(-1,-1) ],
-            ],
-            color: Color::RGB(255, 165, 0),
-            orientation: 0usize,
+        Some(Glyph::Letter(letter)) => {
+            // Letters are drawn once for each size and color, then kept.
+            let key = (letter, glyph_color.r == 0, font.height());
+            if let Entry::Vacant(entry) = skin.letters.entry(key) {
+                let surface = font.render(&letter.to_string()).blended(sdl_color(glyph_color)).unwrap();
+                entry.insert(texture_creator.create_texture_from_surface(&surface).unwrap());
+            }
+            let texture = &skin.letters[&key];
+            let TextureQuery { width: text_width, height: text_height, .. } = texture.query();
+            // Scaled to most of the block's height.
+            let height = size * 4 / 5;
+            let width = text_width as i32 * height / cmp::max(text_height as i32, 1);
+            let letter_rect = Rect::new(rect.x() + (size - width) / 2, rect.y() + (size - height) / 2, width as u32, height as u32);
+            canvas.copy(texture, None, letter_rect).unwrap();
         }
     }
+}
 
-    fn move_by(&mut self, pos: Pos2D) {
-        self.pos.add(pos);
-    }
+fn draw_text<T:RenderTarget, F>(
+    canvas: &mut Canvas<T>, 
+    texture_creator: & TextureCreator<F>, 
+    pos:Pos2D, 
+    text: &str, 
+    font: &Font, 
+    scale_down: u32,
+    color: Color,
+    ) {
+    let surface = font.render(text).blended(color).unwrap();
+    let texture = texture_creator.create_texture_from_surface(&surface).unwrap();
+    let TextureQuery { width: text_width, height: text_height, .. } = texture.query();
+    let rect = Rect::new(pos.x, pos.y, text_width/scale_down, text_height/scale_down);
+    canvas.copy(&texture, None, Some(rect)).unwrap();
+}
 
-    fn rotate_right(&mut self) {
-        self.orientation = (self.orientation + 1) % 4;
-    }
-    fn rotate_left(&mut self) {
-        self.orientation = (self.orientation + 3) % 4;
-    }
+// The music has a channel of its own.
+static MUSIC_CHANNEL: Channel = Channel(0);
 
-    fn iter(&self) -> TetrisPieceIter {
-        TetrisPieceIter {
-            block_num: 0usize,
-            piece: &self,
-        }
-    }
+fn load_chunk(samples: &[i16]) -> Result<Chunk, String> {
+    RWops::from_bytes(&sound::wav(samples))?.load_wav()
 }
 
-struct RandomTetrisPieceGenerator {
-    piece_seq: Vec<i32>,
-    idx: usize,
+// Sound effects and music, through SDL_mixer.
+struct Audio {
+    effects: HashMap<Sound, Chunk>,
+    music: Option<Chunk>,
+    tempo: f32,
 }
 
-impl RandomTetrisPieceGenerator {
-    fn new() -> Self {
-        RandomTetrisPieceGenerator {
-            piece_seq: RandomTetrisPieceGenerator::next_permut(),
-            idx: 0usize,
+impl Audio {
+    fn open(volume: Volume) -> Result<Self, String> {
+        mixer::open_audio(sound::SAMPLE_RATE as i32, mixer::DEFAULT_FORMAT, mixer::DEFAULT_CHANNELS, 1024)?;
+        mixer::allocate_channels(16);
+        mixer::reserve_channels(1);
+        MUSIC_CHANNEL.set_volume(volume.music_level(mixer::MAX_VOLUME as u32) as i32);
+        let mut effects = HashMap::new();
+        for &sound in sound::SOUNDS.iter() {
+            let mut chunk = load_chunk(&sound::effect_samples(sound))?;
+            chunk.set_volume(volume.effects_level(mixer::MAX_VOLUME as u32) as i32);
+            effects.insert(sound, chunk);
         }
+        Ok(Audio {
+            effects: effects,
+            music: None,
+            tempo: 0.0,
+        })
     }
 
-    fn reset(&mut self) {
-        self.idx = 6;
-    }
-    
-    fn next_permut() -> Vec<i32> {
-        // Generate a permutation of every tetris piece.
-        let mut piece_seq: Vec<i32> = (0..7).collect();
-        {
-            let slice: &mut [i32] = &mut piece_seq;
-            thread_rng().shuffle(slice);
-        }
-        piece_seq
-    }
-
-    fn get_next_piece(&mut self, pos: Pos2D) -> TetrisPiece {
-        self.idx = (self.idx + 1) % 7;
-        if self.idx == 0 {
-            self.piece_seq = RandomTetrisPieceGenerator::next_permut();
+    fn play(&self, sounds: &[Sound]) {
+        for sound in sounds {
+            // With every channel busy, the sound is skipped.
+            let _ = Channel::all().play(&self.effects[sound], 0);
         }
-        self.get_piece_for_num(self.piece_seq[self.idx], pos).unwrap()
     }
 
-    fn get_piece_for_num(&self, num: i32, pos: Pos2D) -> Option<TetrisPiece> {
-        match num {
-            0 => Some(TetrisPiece::build_i_piece(pos)),
-            1 => Some(TetrisPiece::build_o_piece(pos)),
-            2 => Some(TetrisPiece::build_s_piece(pos)),
-            3 => Some(TetrisPiece::build_z_piece(pos)),
-            4 => Some(TetrisPiece::build_j_piece(pos)),
-            5 => Some(TetrisPiece::build_l_piece(pos)),
-            6 => Some(TetrisPiece::build_t_piece(pos)),
-            _ => None
+    // Loops the music, starting it over when the tempo changes. If it fails,
+    // it isn't tried again until then.
+    fn play_music(&mut self, tempo: f32) -> Result<(), String> {
+        if tempo == self.tempo {
+            return Ok(());
         }
+        self.tempo = tempo;
+        let music = load_chunk(&sound::music_samples(tempo))?;
+        MUSIC_CHANNEL.play(&music, -1)?;
+        self.music = Some(music);
+        Ok(())
     }
 }
 
-struct TetrisPieceIter<'a> {
-    block_num: usize,
-    piece: &'a TetrisPiece,
+// A player's keys as SDL keycodes, with the actions they do.
+struct KeyBindings {
+    keys: Vec<(Keycode, Action)>,
+    // Soft drop lasts as long as the key is held.
+    soft_drop: Vec<Scancode>,
 }
 
-impl<'a> Iterator for TetrisPieceIter<'a> {
-    type Item = Pos2D;
-
-    fn next(&mut self) -> Option<Pos2D> {
-        if self.block_num == 4 {
-            return None;
+impl KeyBindings {
+    // Keys SDL has no such name for are left out.
+    fn new(action_keys: &ActionKeys) -> Self {
+        let mut keys = Vec::new();
+        for &action in ACTIONS.iter() {
+            for name in action_keys.keys(action) {
+                match Keycode::from_name(name) {
+                    Some(keycode) => keys.push((keycode, action)),
+                    None => println!("Unknown key {} for {}", name, action.name()),
+                }
+            }
         }
-        let diff = &self.piece.shape[self.piece.orientation][self.block_num];
-        let mut pos = Pos2D::xy(self.piece.pos.x, self.piece.pos.y);
-
-        pos.x = pos.x + diff.x;
-        pos.y = pos.y + diff.y;
-
-        self.block_num = self.block_num + 1;
-
-        Some(pos)
-    }
-}
-
-
-impl Drawable for TetrisPiece {
-    fn draw<T:RenderTarget>(&self, canvas: &mut Canvas<T>, pos:Pos2D) {
-        let box_width = 20;
-        canvas.set_draw_color(self.color);
-        for diff in self.shape[self.orientation].iter() {
-            let rect = Rect::new(
-                (pos.x + diff.x * box_width) + 1, 
-                (pos.y + diff.y * box_width) + 1, 
-                (box_width - 2) as u32, 
-                (box_width - 2) as u32
-            );
-            canvas.fill_rect(rect);
+        let soft_drop = keys.iter()
+            .filter(|&&(_, action)| action == Action::SoftDrop)
+            .filter_map(|&(keycode, _)| Scancode::from_keycode(keycode))
+            .collect();
+        KeyBindings {
+            keys: keys,
+            soft_drop: soft_drop,
         }
     }
-}
 
-struct Input {
-    left_key_pressed: bool,
-    right_key_pressed: bool,
-    up_key_pressed:bool,
-    down_key_pressed:bool,
-}
+    fn actions(&self, keycode: Keycode) -> Vec<Action> {
+        self.keys.iter().filter(|&&(key, _)| key == keycode).map(|&(_, action)| action).collect()
+    }
 
-impl Input {
-    fn reset(&mut self) {
-        self.left_key_pressed = false;
-        self.right_key_pressed = false;
-        self.up_key_pressed = false;
-        self.down_key_pressed = false;
+    fn key_down(&self, input: &mut Input, keycode: Keycode, repeat: bool) {
+        controls::press(input, &self.actions(keycode), repeat);
     }
 }
 
-struct TetrisUnitBlock {
-    is_filled: bool,
-    color: Color,
+// One set of keys per player.
+fn key_bindings(game: &Game, controls: &Controls) -> Vec<KeyBindings> {
+    controls.players(game).into_iter().map(KeyBindings::new).collect()
 }
 
-struct TetrisBoard {
-    width: usize,
-    height: usize,
-    board: Vec<Vec<TetrisUnitBlock>>,
-    active_piece: TetrisPiece,
-    tetris_gen: RandomTetrisPieceGenerator,
-    gravity: u32,
-    gravity_countdown: u32,
-    lock_delay: u32,
-    lock_delay_countdown: u32,
-    lines_cleared: u32,
-    is_game_over: bool,
-    game_over_delay: u32,
-    game_over_countdown: u32,
-    locking_state: bool,
-    level: u32,
+// What the keys do, for the help text. Pausing and restarting are left out
+// in versus, for room between the boards.
+fn help(game: &Game, controls: &Controls) -> Vec<Vec<String>> {
+    let players = controls.players(game);
+    let is_known = |name: &str| Keycode::from_name(name).is_some();
+    let mut help: Vec<Vec<String>> = players.iter().map(|keys| keys.help(&is_known, players.len() == 1)).collect();
+    help[0].push("F1 for controls".to_string());
+    help
 }
 
-
-impl TetrisBoard {
-
-    fn new() -> Self {
-
-        let mut board: Vec<Vec<TetrisUnitBlock>> = Vec::new();
-        let width: usize = tetris_board_width;
-        let height: usize = tetris_board_height;
-
-        for i in 0usize..height {
-            board.push(Vec::new());
-            for _ in 0usize..width {
-                board[i].push(TetrisUnitBlock { is_filled: false, color: Color::RGB(0,0,0) } );
-            }
-        }
-
-        for i in 0usize..width {
-            board[0][i] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-            board[height-1][i] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-        }
-        for i in 0usize..height {
-            board[i][0] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-            board[i][width-1] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-        }
-
-        let mut randomTetrisPieceGenerator = RandomTetrisPieceGenerator::new();
-
-        TetrisBoard {
-            width: width,
-            height: height,
-            board: board,
-            active_piece: randomTetrisPieceGenerator.get_next_piece(start_pos),
-            tetris_gen: randomTetrisPieceGenerator,
-            gravity: 20,
-            gravity_countdown:  20,
-            lock_delay: 30,
-            lock_delay_countdown: 30,
-            lines_cleared: 0,
-            is_game_over: false,
-            game_over_delay: 60,
-            game_over_countdown: 0,
-            locking_state: false,
-            level: 1,
-        }
-    }
-
-    fn is_valid(&self) -> bool {
-        for pos in self.active_piece.iter() {
-            if self.board[pos.y as usize][pos.x as usize].is_filled {
-                return false;
+// `font` has to be loaded at the layout's font size.
+fn draw_frame<'a, T:RenderTarget, F>(canvas: &mut Canvas<T>, texture_creator: &'a TextureCreator<F>, layout: &Layout, skin: &mut Skin<'a>, frame: &Frame, font: &Font) {
+    let box_width = layout.box_width;
+    canvas.set_draw_color(sdl_color(skin.theme.background_color));
+    canvas.clear();
+    // Everything moves when the screen shakes.
+    let shaken = Layout {
+        box_width: box_width,
+        offset: Pos2D::xy(layout.offset.x + (frame.shake.0 * box_width as f32) as i32, layout.offset.y + (frame.shake.1 * box_width as f32) as i32),
+    };
+    let layout = &shaken;
+    // Over the screen rather than the whole window, to keep its shape.
+    if let Some(ref background) = skin.background {
+        let top_left = layout.pixel(0, 0);
+        let screen = Rect::new(top_left.x, top_left.y, (box_width * SCREEN_WIDTH) as u32, (box_width * SCREEN_HEIGHT) as u32);
+        canvas.copy(background, None, screen).unwrap();
+    }
+
+    for board in frame.boards.iter() {
+        for (y, row) in board.blocks.iter().enumerate() {
+            for (x, block) in row.iter().enumerate() {
+                if let Some(ref block) = *block {
+                    draw_block(canvas, texture_creator, font, layout, skin, Pos2D::xy(board.pos.x + x as i32, board.pos.y + y as i32), block);
+                }
             }
         }
-        true
-    }
-
-    fn move_active_piece(&mut self, pos: Pos2D) -> bool {
-        self.active_piece.move_by(pos);
-
-        if !self.is_valid() {
-            self.active_piece.move_by(pos.inv());
-            false
-        } else {
-            true
+        // Incoming garbage, stacked up from the bottom of the board.
+        if let Some(incoming) = board.incoming {
+            let bottom = layout.pixel(board.pos.x, board.pos.y + board.height() - 1);
+            let meter_height = cmp::min(incoming as i32, board.height() - 2) * box_width;
+            canvas.set_draw_color(Color::RGB(255, 0, 0));
+            canvas.fill_rect(Rect::new(bottom.x - box_width * 3 / 5, bottom.y - meter_height, (box_width * 2 / 5) as u32, meter_height as u32)).unwrap();
         }
     }
 
-    fn rotate_active_piece_right(&mut self) -> bool {
-        self.active_piece.rotate_right();
-
-        if !self.is_valid() {
-            self.active_piece.rotate_left();
-            false
-        } else {
-            true
-        }
-
-    }
-
-    fn consume(&mut self, piece: TetrisPiece) {
-        for pos in piece.iter() {
-            self.board[pos.y as usize][pos.x as usize].is_filled = true;
-            self.board[pos.y as usize][pos.x as usize].color = piece.color;
-        }
-    }
-
-    fn is_row_full(&self, row: usize) -> bool {
-        for i in 1..self.width-1 {
-            if !self.board[row][i].is_filled {
-                return false;
-            }
+    for piece in frame.pieces.iter() {
+        for cell in piece.cells.iter() {
+            draw_block(canvas, texture_creator, font, layout, skin, Pos2D::xy(piece.pos.x + cell.x, piece.pos.y + cell.y), &piece.block);
         }
-        true
     }
 
-    fn is_row_empty(&self, row:usize) -> bool {
-        for i in 0..self.width-1 {
-            if self.board[row][i].is_filled {
-                return false;
-            }
-        }
-        true
+    let particle_size = cmp::max(box_width / 4, 2);
+    for particle in frame.particles.iter() {
+        canvas.set_draw_color(sdl_color(particle.color));
+        let x = layout.offset.x + (particle.x * box_width as f32) as i32 - particle_size / 2;
+        let y = layout.offset.y + (particle.y * box_width as f32) as i32 - particle_size / 2;
+        canvas.fill_rect(Rect::new(x, y, particle_size as u32, particle_size as u32)).unwrap();
     }
 
-    fn shift_down(&mut self, row:usize) {
-        for i in (2..row+1).rev() {
-            for j in (1..self.width-1) {
-                self.board[i][j].is_filled = self.board[i-1][j].is_filled;
-                self.board[i][j].color = self.board[i-1][j].color;
+    for panel in frame.panels.iter() {
+        let mut y = panel.pos.y;
+        for line in panel.lines.iter() {
+            if !line.text.is_empty() {
+                let scale_down = match line.size {
+                    TextSize::Small => 3,
+                    TextSize::Medium => 2,
+                    TextSize::Large => 1,
+                };
+                draw_text(canvas, texture_creator, layout.pixel(panel.pos.x, y), &line.text, font, scale_down, sdl_color(line.color));
             }
+            y += line.size.rows();
         }
     }
+}
 
-    fn clear_lines(&mut self) {
-        for i in (1..self.height-1).rev() {
-            while self.is_row_full(i) {
-                self.lines_cleared += 1;
-                self.shift_down(i);
-            }
-        }
-    }
-
-    fn reset(&mut self) {
-        for i in 0usize..self.width {
-            for j in 0usize ..self.height {
-                self.board[j][i] = TetrisUnitBlock {is_filled: false, color: Color::RGB(0,0,0)};
-            }
-        }
-        for i in 0usize..self.width {
-            self.board[0][i] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-            self.board[self.height-1][i] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-        }
-        for i in 0usize..self.height {
-            self.board[i][0] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-            self.board[i][self.width-1] = TetrisUnitBlock { is_filled:true, color: Color::RGB(255,255,255) };
-        }
-
-        self.tetris_gen.reset();
-        self.active_piece = self.tetris_gen.get_next_piece(start_pos);
-        self.gravity= 20;
-        self.gravity_countdown=  20;
-        self.lock_delay= 30;
-        self.lock_delay_countdown= 30;
-        self.lines_cleared= 0;
-        self.is_game_over= false;
-        self.game_over_delay= 60;
-        self.game_over_countdown= 0;
-        self.locking_state = false;
-        self.level = 1;
+// Draws on a surface in memory rather than a window, so it works without a
+// display.
+fn draw_offscreen<'a>(canvas: &mut Canvas<Surface>, texture_creator: &'a TextureCreator<SurfaceContext>, skin: &mut Skin<'a>, frame: &Frame, font: &Font) {
+    let layout = Layout::fit(canvas.surface().width(), canvas.surface().height());
+    draw_frame(canvas, texture_creator, &layout, skin, frame, font);
+}
 
+fn draw_snapshot(frame: &Frame, font: &Font, theme: &Theme, width: u32, height: u32) -> Result<Surface<'static>, String> {
+    let mut canvas = Surface::new(width, height, PixelFormatEnum::RGB24)?.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    {
+        let mut skin = Skin::load(theme, &texture_creator)?;
+        draw_offscreen(&mut canvas, &texture_creator, &mut skin, frame, font);
     }
+    Ok(canvas.into_surface())
+}
 
-
-
-    fn update(&mut self, input: &Input) {
-
-        if self.is_game_over {
-            if (self.game_over_countdown > 0) {
-                self.game_over_countdown -= 1;
-            }
-            if self.game_over_countdown == 0 && input.up_key_pressed {
-                self.reset();
-            }
-            return;
-        }
-
-        // Handle Input
-        if (input.left_key_pressed) {
-            self.move_active_piece(Pos2D::xy(-1, 0));
-        } 
-        if (input.right_key_pressed) {
-            self.move_active_piece(Pos2D::xy(1, 0));
-        }
-        if (input.up_key_pressed) {
-            self.rotate_active_piece_right();
-        }
-
-        if (input.down_key_pressed) {
-            self.gravity = 2;
-        } else {
-            self.gravity = 3 * (11 - self.level);
-        }
-
-        // Countdown the timers.
-        if (self.gravity_countdown > 0) {
-            self.gravity_countdown -= 1;
-        }
-        if (self.lock_delay_countdown > 0 && self.locking_state) {
-            self.lock_delay_countdown -= 1;
-        }
-
-
-        let mut move_down_success = true;
-
-        // Move piece down if gravity countdown is done.
-        if self.gravity_countdown == 0 || self.locking_state {
-            self.gravity_countdown = self.gravity;
-            move_down_success = self.move_active_piece(Pos2D::xy(0,1));
-
-            // Reset lock delay if piece moved down.
-            if move_down_success {
-                self.lock_delay_countdown = self.lock_delay;
-                self.game_over_countdown = self.game_over_delay;
-                self.locking_state = false;
-            } else {
-                self.locking_state = true;
-            }
-        }
-
-        if self.locking_state && self.lock_delay_countdown == 0 {
-            let piece_to_consume = mem::replace(&mut self.active_piece, self.tetris_gen.get_next_piece(start_pos));
-            self.consume(piece_to_consume);
-            self.clear_lines();
-
-            if !self.is_valid() {
-                self.is_game_over = true;
-            }
-
-            self.lock_delay_countdown = self.lock_delay;
-            self.locking_state = false;
-        }
-
-        self.level = (self.lines_cleared / 10) + 1;
-        if (self.level >= 10) {
-            self.level = 10;
-        }
-
-    }
+fn save_snapshot(frame: &Frame, font: &Font, theme: &Theme, width: u32, height: u32, path: &str) -> Result<(), String> {
+    draw_snapshot(frame, font, theme, width, height)?.save(path)
 }
 
-impl Drawable for TetrisUnitBlock {
-    fn draw<T:RenderTarget>(&self, canvas: &mut Canvas<T>, pos:Pos2D) {
-        let box_width = 20;
-        canvas.set_draw_color(self.color);
-        let rect = Rect::new(
-                pos.x + 1,  
-                pos.y + 1, 
-                box_width - 2 as u32, 
-                box_width - 2 as u32
-        );
-        canvas.fill_rect(rect);
-    }
+// How a replay is turned into an animated GIF or APNG: drawn `width` by
+// `height`, resized by `scale`, keeping every `frame_skip`th frame.
+struct ExportOptions {
+    path: String,
+    width: u32,
+    height: u32,
+    frame_skip: u32,
+    scale: f64,
+    effects: EffectSettings,
 }
 
-impl Drawable for TetrisBoard {
-    fn draw<T:RenderTarget>(&self, canvas: &mut Canvas<T>, pos:Pos2D) {
-        let box_width: i32 = 20;
-        for i in 0usize..self.board.len() {
-            for j in 0usize..self.board[i].len() {
-                let x:i32 = (j as i32) * box_width + pos.x;
-                let y:i32 = (i as i32) * box_width + pos.y;
-                self.board[i][j].draw(canvas, Pos2D::xy(x, y))
+// Plays a replay back as fast as it goes, for an animation.
+fn export_replay(replay: &Replay, font: &Font, theme: &Theme, options: ExportOptions) -> Result<(), String> {
+    let ExportOptions { path, width, height, frame_skip, scale, effects } = options;
+    let ruleset = Ruleset::new(&replay.ruleset, replay.seed).ok_or(format!("Unknown ruleset {}", replay.ruleset))?;
+    let mut game = Game::from_ruleset(ruleset);
+    let mut player = ReplayPlayer::new(replay);
+    let mut finesse = FinesseTracker::new();
+    let mut effects = Effects::new(effects);
+
+    // The start, then a frame after every `frame_skip` inputs, and one more
+    // for any left over so the animation ends where the replay does.
+    let inputs = replay.inputs.len() as u32;
+    let frames = inputs.div_ceil(frame_skip) + 1;
+    let mut animation = AnimationWriter::create(&path, width, height, scale, frame_skip, frames)?;
+    let mut canvas = Surface::new(width, height, PixelFormatEnum::RGB24)?.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let mut skin = Skin::load(theme, &texture_creator)?;
+    for i in 0..frames {
+        if i > 0 {
+            for _ in (i - 1) * frame_skip..cmp::min(i * frame_skip, inputs) {
+                let input = player.next_input(game.board().unwrap());
+                game.update(&[input]);
+                finesse.update(game.board().unwrap(), &input);
+                effects.update(&game);
             }
         }
-
-        let x = self.active_piece.pos.x * box_width + pos.x;
-        let y = self.active_piece.pos.y * box_width + pos.y;
-        self.active_piece.draw(canvas, Pos2D::xy(x,y));
+        let mut frame = Frame::new(&game, &finesse, &Controls::default(), &[]);
+        effects.apply(&mut frame);
+        draw_offscreen(&mut canvas, &texture_creator, &mut skin, &frame, font);
+        let surface = canvas.surface();
+        let pixels = surface.without_lock().ok_or("Failed to read the pixels")?;
+        animation.write_frame(pixels, surface.pitch() as usize)?;
     }
+    animation.finish()
 }
 
-fn draw_text<T:RenderTarget, F>(
-    canvas: &mut Canvas<T>, 
-    texture_creator: & TextureCreator<F>, 
-    pos:Pos2D, 
-    text: &str, 
-    font: &Font, 
-    scale_down: u32,
-    color: Color,
-    ) {
-    let surface = font.render(text).blended(color).unwrap();
-    let texture = texture_creator.create_texture_from_surface(&surface).unwrap();
-    let TextureQuery { width: text_width, height: text_height, .. } = texture.query();
-    let rect = Rect::new(pos.x, pos.y, text_width/scale_down, text_height/scale_down);
-    canvas.copy(&texture, None, Some(rect)).unwrap();
-}
-
-
 fn main() {
     let width = 800;
     let height = 600;
 
+    let ttf_context = sdl2::ttf::init().unwrap();
+    let _image_context = sdl2::image::init(sdl2::image::INIT_PNG | sdl2::image::INIT_JPG).unwrap();
+    let font_path: &Path = Path::new("res/fonts/kenney_future.ttf"); 
+    let mut layout = Layout::fit(width, height);
+    let mut font = ttf_context.load_font(font_path, layout.font_size()).unwrap();
+
+    let mut args: Vec<String> = env::args().collect();
+    game::load_pieces(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let effect_settings = game::take_option(&mut args, "--effects")
+        .map_or(Ok(EffectSettings::all()), |names| EffectSettings::from_names(&names))
+        .unwrap_or_else(|e| panic!("{}", e));
+    let mut volume = Volume::new();
+    let volume_option = |args: &mut Vec<String>, name: &str, default: u32| {
+        game::take_option(args, name).map_or(default, |s| s.parse().unwrap_or_else(|_| panic!("{} needs a number from 0 to 100", name)))
+    };
+    volume.master = volume_option(&mut args, "--volume", volume.master);
+    volume.music = volume_option(&mut args, "--music-volume", volume.music);
+    volume.effects = volume_option(&mut args, "--sfx-volume", volume.effects);
+    // Saved back to from the controls menu.
+    let controls_path = game::take_option(&mut args, "--controls").unwrap_or("controls.toml".to_string());
+    let mut controls = Controls::load(&controls_path).unwrap_or_else(|e| panic!("{}", e));
+    // `--theme` and `--glyphs` beat the ones picked in the menu.
+    let mut theme_name = game::take_option(&mut args, "--theme").unwrap_or(controls.theme.clone());
+    let mut glyphs = game::take_option(&mut args, "--glyphs")
+        .map(|name| Glyphs::from_name(&name).expect("--glyphs is none, letters or patterns"))
+        .or(controls.glyphs);
+    let mut theme = Theme::named(&theme_name).unwrap_or_else(|e| panic!("{}", e));
+    theme.glyphs = glyphs.unwrap_or(theme.glyphs);
+    // Saves the first frame instead of playing.
+    let snapshot_path = game::take_option(&mut args, "--snapshot");
+
+    // Or turns a replay into an animation.
+    let export_path = game::take_option(&mut args, "--export");
+    let replay_path = game::take_option(&mut args, "--replay");
+    let frame_skip = game::take_option(&mut args, "--frame-skip").map_or(2, |s| s.parse().expect("--frame-skip needs a number"));
+    let scale = game::take_option(&mut args, "--scale").map_or(0.5, |s| s.parse().expect("--scale needs a number"));
+    if let Some(path) = export_path {
+        let replay_path = replay_path.expect("--export needs a --replay to export");
+        assert!(frame_skip > 0 && scale > 0.0, "--frame-skip and --scale have to be more than 0");
+        let replay = Replay::load(&replay_path).expect("Failed to load the replay");
+        let options = ExportOptions {
+            path: path.clone(),
+            width: width,
+            height: height,
+            frame_skip: frame_skip,
+            scale: scale,
+            effects: effect_settings,
+        };
+        export_replay(&replay, &font, &theme, options).unwrap_or_else(|e| panic!("{}", e));
+        println!("Saved {}", path);
+        return;
+    }
+
+    let mut game = Game::from_args(&args);
+
+    let mut finesse = FinesseTracker::new();
+    let mut effects = Effects::new(effect_settings);
+
+    let mut key_bindings = key_bindings(&game, &controls);
+    let mut help = help(&game, &controls);
+
+    if let Some(path) = snapshot_path {
+        save_snapshot(&Frame::new(&game, &finesse, &controls, &help), &font, &theme, width, height, &path).unwrap_or_else(|e| panic!("{}", e));
+        return;
+    }
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let ttf_context = sdl2::ttf::init().unwrap();
 
     let window = video_subsystem.window("Rust Tetris", width, height)
         .position_centered()
         .opengl()
+        .resizable()
+        .allow_highdpi()
         .build()
         .unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
     let texture_creator = canvas.texture_creator();
+    let mut skin = Skin::load(&theme, &texture_creator).unwrap_or_else(|e| panic!("{}", e));
     let mut event_pump = sdl_context.event_pump().unwrap();
-
-    let font_path: &Path = Path::new("res/fonts/kenney_future.ttf"); 
-    let mut font = ttf_context.load_font(font_path, 28).unwrap();
-
-    let mut tetris_board = TetrisBoard::new();
+    // The game plays on in silence without an audio device.
+    let mut audio = Audio::open(volume).map_err(|e| println!("No sound: {}", e)).ok();
+    let mut sound_tracker = SoundTracker::new();
     let mut last_updated = Instant::now();
 
-
-    let mut input : Input = Input {
-            left_key_pressed: false,
-            right_key_pressed: false,
-            up_key_pressed: false,
-            down_key_pressed: false,
-    };
+    let mut inputs: Vec<Input> = key_bindings.iter().map(|_| Input::new()).collect();
+    let mut is_paused = false;
+    // The game waits while the controls menu is open.
+    let mut menu: Option<ControlsMenu> = None;
+    let menu_instructions: Vec<String> = ["Up, Down to pick a row", "Left, Right to change the page",
+                                          "Enter to add a key or change a setting", "Backspace to clear the keys",
+                                          "Esc to save and go back"].iter().map(|line| line.to_string()).collect();
 
     'running: loop {
 
         for event in event_pump.poll_iter() {
+            let mut close_menu = false;
             match event {
-                Event::Quit {..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+                Event::Quit {..} => {
                     break 'running
                 }
-                Event::KeyDown {keycode: Some(Keycode::Left), ..}  => {
-                    input.left_key_pressed = true;
+                Event::KeyDown {keycode: Some(keycode), repeat: false, ..} if menu.is_some() => {
+                    let menu = menu.as_mut().unwrap();
+                    match keycode {
+                        Keycode::Escape if menu.is_waiting => menu.is_waiting = false,
+                        _ if menu.is_waiting => {
+                            if let Err(e) = menu.add_key(&mut controls, &keycode.name()) {
+                                println!("{}", e);
+                            }
+                        }
+                        Keycode::Up => menu.move_by(-1, 0),
+                        Keycode::Down => menu.move_by(1, 0),
+                        Keycode::Left => menu.move_by(0, -1),
+                        Keycode::Right => menu.move_by(0, 1),
+                        Keycode::Return => menu.select(&mut controls),
+                        Keycode::Backspace | Keycode::Delete => menu.clear_keys(&mut controls),
+                        Keycode::Escape | Keycode::F1 => close_menu = true,
+                        _ => {}
+                    }
+                }
+                Event::KeyDown {..} if menu.is_some() => {}
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+                    break 'running
+                }
+                Event::KeyDown {keycode: Some(Keycode::F1), repeat: false, ..} => {
+                    menu = Some(ControlsMenu::new());
+                }
+                Event::KeyDown {keycode: Some(Keycode::H), repeat: false, ..} => {
+                    if let Game::Pc(ref mut pc_game) = game {
+                        pc_game.show_hints = !pc_game.show_hints;
+                    }
+                }
+                Event::KeyDown {keycode: Some(Keycode::E), repeat: false, ..} => {
+                    effects.show = !effects.show;
                 }
-                Event::KeyDown {keycode: Some(Keycode::Right), ..}  => {
-                    input.right_key_pressed = true;
+                // Share the board as a fumen.
+                Event::KeyDown {keycode: Some(Keycode::F), repeat: false, ..} if game.board().is_some() => {
+                    match Page::from_board(game.board().unwrap(), None) {
+                        Ok(page) => {
+                            let data = fumen::encode(&[page]);
+                            println!("{}", data);
+                            if let Err(e) = video_subsystem.clipboard().set_clipboard_text(&data) {
+                                println!("Failed to copy the fumen: {}", e);
+                            }
+                        }
+                        Err(e) => println!("{}", e),
+                    }
                 }
-                Event::KeyDown {keycode: Some(Keycode::Up), ..}  => {
-                    input.up_key_pressed = true;
+                // A picture of the screen, for bug reports.
+                Event::KeyDown {keycode: Some(Keycode::P), repeat: false, ..} => {
+                    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+                    let path = format!("snapshot-{}.png", seconds);
+                    let (window_width, window_height) = canvas.output_size().unwrap();
+                    let mut frame = Frame::new(&game, &finesse, &controls, &help);
+                    effects.apply(&mut frame);
+                    match save_snapshot(&frame, &font, &theme, window_width, window_height, &path) {
+                        Ok(()) => println!("Saved {}", path),
+                        Err(e) => println!("Failed to save {}: {}", path, e),
+                    }
+                }
+                // Fullscreen at the desktop's resolution, or back to a window.
+                Event::KeyDown {keycode: Some(Keycode::F11), repeat: false, ..} => {
+                    let fullscreen = match canvas.window().fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    canvas.window_mut().set_fullscreen(fullscreen).unwrap();
+                }
+                Event::KeyDown {keycode: Some(keycode), repeat, ..}  => {
+                    let actions: Vec<Action> = key_bindings.iter().flat_map(|keys| keys.actions(keycode)).collect();
+                    if !repeat && actions.contains(&Action::Pause) {
+                        is_paused = !is_paused;
+                    } else if !repeat && actions.contains(&Action::Restart) {
+                        game.restart();
+                        is_paused = false;
+                    } else if !is_paused {
+                        for (input, keys) in inputs.iter_mut().zip(key_bindings.iter()) {
+                            keys.key_down(input, keycode, repeat);
+                        }
+                    }
                 }
                 _ => {}
             }
+            if close_menu {
+                match controls.save(&controls_path) {
+                    Ok(()) => println!("Saved {}", controls_path),
+                    Err(e) => println!("{}", e),
+                }
+                key_bindings = self::key_bindings(&game, &controls);
+                help = self::help(&game, &controls);
+                menu = None;
+                if controls.theme != theme_name || controls.glyphs != glyphs {
+                    match Theme::named(&controls.theme) {
+                        Ok(mut new_theme) => {
+                            new_theme.glyphs = controls.glyphs.unwrap_or(new_theme.glyphs);
+                            drop(skin);
+                            theme = new_theme;
+                            skin = Skin::load(&theme, &texture_creator).unwrap_or_else(|e| panic!("{}", e));
+                            theme_name = controls.theme.clone();
+                            glyphs = controls.glyphs;
+                        }
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
         }
 
-        if (event_pump.keyboard_state().is_scancode_pressed(Scancode::Down)) {
-            input.down_key_pressed = true;
-        }
-        else {
-            input.down_key_pressed = false;
+        for (input, keys) in inputs.iter_mut().zip(key_bindings.iter()) {
+            let keyboard = event_pump.keyboard_state();
+            input.down_key_pressed = keys.soft_drop.iter().any(|&scancode| keyboard.is_scancode_pressed(scancode));
         }
 
         let current_time = Instant::now();
 
         if current_time.duration_since(last_updated) > Duration::new(0, 1_000_000_000 / 60) {
-            tetris_board.update(&input);
-            input.reset();
+            if !is_paused && menu.is_none() {
+                game.update(&inputs);
+                if let Some(board) = game.board() {
+                    finesse.update(board, &inputs[0]);
+                }
+                effects.update(&game);
+                let sounds = sound_tracker.update(&game);
+                if let Some(ref mut audio) = audio {
+                    audio.play(&sounds);
+                    if let Err(e) = audio.play_music(sound::music_tempo(&game)) {
+                        println!("Failed to play the music: {}", e);
+                    }
+                }
+            }
+            for input in inputs.iter_mut() {
+                input.reset();
+            }
             last_updated = current_time;
 
-            canvas.set_draw_color(Color::RGB(0,0,0));
-            canvas.fill_rect(Rect::new(0,0,width,height));
-
-            tetris_board.draw(&mut canvas, Pos2D::xy(250,50));
-
-            draw_text(&mut canvas, &texture_creator, Pos2D::xy(50,10), &format!("Left, Right to move " ), &font, 3, Color::RGB(255,255,255));
-            draw_text(&mut canvas, &texture_creator, Pos2D::xy(50,20), &format!("Up to rotate" ), &font, 3, Color::RGB(255,255,255));
-            draw_text(&mut canvas, &texture_creator, Pos2D::xy(50,30), &format!("Down to drop" ), &font, 3, Color::RGB(255,255,255));
-            draw_text(&mut canvas, &texture_creator, Pos2D::xy(500,10), &format!("Lines : {}", tetris_board.lines_cleared), &font, 1, Color::RGB(255,255,255));
-            draw_text(&mut canvas, &texture_creator, Pos2D::xy(500,40), &format!("Level : {}", tetris_board.level), &font, 1, Color::RGB(255,255,255));
-            draw_text(&mut canvas, &texture_creator, Pos2D::xy(300,10), "Tetris", &font, 1, Color::RGB(255,255,255));
-
-            if tetris_board.is_game_over {
-                draw_text(&mut canvas, &texture_creator, Pos2D::xy(280,300), "GAME OVER!", &font, 1, Color::RGB(255, 0, 0));
-                draw_text(&mut canvas, &texture_creator, Pos2D::xy(140,340), "Press UP arrow key to restart", &font, 1, Color::RGB(128, 0, 0));
+            // In pixels, which is more than the window's size on high-DPI
+            // displays.
+            let (window_width, window_height) = canvas.output_size().unwrap();
+            let new_layout = Layout::fit(window_width, window_height);
+            if new_layout.box_width != layout.box_width {
+                font = ttf_context.load_font(font_path, new_layout.font_size()).unwrap();
             }
+            layout = new_layout;
+
+            let frame = match menu {
+                Some(ref menu) => Frame::controls_menu(&controls, menu, &menu_instructions),
+                None => {
+                    let mut frame = Frame::new(&game, &finesse, &controls, &help);
+                    effects.apply(&mut frame);
+                    if is_paused {
+                        let keys = controls.players(&game).iter().flat_map(|keys| keys.pause.first()).next().cloned();
+                        frame.pause(&format!("Press {} to resume", keys.unwrap_or_default()));
+                    }
+                    frame
+                }
+            };
+            draw_frame(&mut canvas, &texture_creator, &layout, &mut skin, &frame, &font);
 
             canvas.present();
         }
 
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use sdl2::image::{self, LoadSurface, SaveSurface};
+    use sdl2::surface::Surface;
+
+    use rust_tetris::{RandomTetrisPieceGenerator, TetrisBoard};
+    use rust_tetris::controls::Controls;
+    use rust_tetris::finesse::FinesseTracker;
+    use rust_tetris::frame::Frame;
+    use rust_tetris::fumen;
+    use rust_tetris::game::Game;
+    use rust_tetris::sound::{self, Volume};
+    use rust_tetris::theme::{Glyphs, Theme};
+    use super::{draw_snapshot, Audio, Layout};
+
+    // A T over an L, a J, an S, an O and an I on two rows of garbage.
+    static FUMEN: &str = "v115@zghlEeRpAeglBeg0AeR4RpAegli0R4zhC8AeJ8AeE8?JeV7H";
+
+    // The pieces dealt, so the queue is the same every time.
+    fn seeded(mut board: TetrisBoard, seed: usize) -> TetrisBoard {
+        board.tetris_gen = RandomTetrisPieceGenerator::with_seed(seed);
+        board.active_piece = board.tetris_gen.get_next_piece(board.spawn_pos);
+        board
+    }
+
+    fn pixels(surface: &Surface) -> Vec<u8> {
+        let row = surface.width() as usize * 3;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock(|pixels| (0..surface.height() as usize).flat_map(|y| pixels[y * pitch..y * pitch + row].to_vec()).collect())
+    }
+
+    // Snapshots of each game's first frame, drawn as the window draws them,
+    // against the images in tests/golden. Set UPDATE_GOLDEN to write the
+    // images instead, then look at them before checking them in. One test,
+    // as SDL_ttf can't be used from more than one thread.
+    #[test]
+    fn snapshots_match_golden_images() {
+        let ttf_context = sdl2::ttf::init().unwrap();
+        let _image_context = image::init(image::INIT_PNG).unwrap();
+        let (width, height) = (800, 600);
+        let font = ttf_context.load_font("res/fonts/kenney_future.ttf", Layout::fit(width, height).font_size()).unwrap();
+
+        let page = &fumen::decode(FUMEN).unwrap()[0];
+        let mut fumen_board = seeded(page.to_board(), 2);
+        fumen_board.active_piece = page.to_board().active_piece;
+        let mut bevel = Theme::named("bevel").unwrap();
+        bevel.glyphs = Glyphs::Letters;
+        let mut patterns = Theme::plain();
+        patterns.glyphs = Glyphs::Patterns;
+        let snapshots = [
+            ("empty", Game::Marathon(TetrisBoard::with_seed(1)), Theme::plain()),
+            // With the bevel theme's images and letters on the pieces.
+            ("fumen", Game::Marathon(fumen_board), bevel),
+            ("big", Game::Marathon(seeded(TetrisBoard::big(), 3)), patterns),
+        ];
+        for &(name, ref game, ref theme) in snapshots.iter() {
+            let frame = Frame::new(game, &FinesseTracker::new(), &Controls::default(), &[]);
+            let surface = draw_snapshot(&frame, &font, theme, width, height).unwrap();
+            let path = format!("tests/golden/{}.png", name);
+            if env::var("UPDATE_GOLDEN").is_ok() {
+                fs::create_dir_all("tests/golden").unwrap();
+                surface.save(&path).unwrap();
+                continue;
+            }
+            let golden = Surface::from_file(&path)
+                .and_then(|golden| golden.convert(&surface.pixel_format()))
+                .unwrap_or_else(|e| panic!("Failed to load {}, run with UPDATE_GOLDEN=1 to make it: {}", path, e));
+            assert_eq!((golden.width(), golden.height()), (width, height));
+            assert!(pixels(&surface) == pixels(&golden), "{} doesn't match {}", name, path);
+        }
+    }
+
+    // The dummy driver needs no sound card, so this runs anywhere SDL does.
+    #[test]
+    fn opens_audio() {
+        env::set_var("SDL_AUDIODRIVER", "dummy");
+        let sdl_context = sdl2::init().unwrap();
+        let _audio_subsystem = sdl_context.audio().unwrap();
+        let mut audio = Audio::open(Volume::new()).unwrap();
+        assert_eq!(audio.effects.len(), sound::SOUNDS.len());
+        audio.play(&sound::SOUNDS);
+        audio.play_music(1.0).unwrap();
+        assert!(audio.music.is_some());
+        // The same tempo leaves the music playing.
+        audio.play_music(1.0).unwrap();
+        audio.play_music(1.12).unwrap();
+        assert_eq!(audio.tempo, 1.12);
+    }
+}
@@ -0,0 +1,200 @@
+use std::cmp;
+
+use super::{Input, Speed, TetrisBoard};
+
+// Internal gravity by level, in 1/256ths of a row per frame.
+static GRAVITY_CURVE: [(u32, u32); 30] = [
+    (0, 4), (30, 6), (35, 8), (40, 10), (50, 12), (60, 16), (70, 32), (80, 48),
+    (90, 64), (100, 80), (120, 96), (140, 112), (160, 128), (170, 144), (200, 4),
+    (220, 32), (230, 64), (233, 96), (236, 128), (239, 160), (243, 192), (247, 224),
+    (251, 256), (300, 512), (330, 768), (360, 1024), (400, 1280), (420, 1024),
+    (450, 768), (500, 5120),
+];
+
+// (level, ARE, line clear delay, lock delay) by section.
+static DELAY_CURVE: [(u32, u32, u32, u32); 6] = [
+    (0, 25, 40, 30),
+    (500, 25, 25, 30),
+    (600, 25, 16, 30),
+    (700, 16, 12, 30),
+    (800, 12, 6, 30),
+    (900, 12, 6, 17),
+];
+
+// Score needed for each grade.
+static GRADES: [(u32, &str); 18] = [
+    (0, "9"), (400, "8"), (800, "7"), (1400, "6"), (2000, "5"), (3500, "4"),
+    (5500, "3"), (8000, "2"), (12000, "1"), (16000, "S1"), (22000, "S2"),
+    (30000, "S3"), (40000, "S4"), (52000, "S5"), (66000, "S6"), (82000, "S7"),
+    (100000, "S8"), (120000, "S9"),
+];
+
+// (level, score, frames) the player must reach in time to stay in the running for GM.
+static GM_CHECKPOINTS: [(u32, u32, u32); 3] = [
+    (300, 12000, 15300),
+    (500, 40000, 27000),
+    (999, 126000, 48600),
+];
+
+static MAX_LEVEL: u32 = 999;
+
+// TGM style master mode: levels 0 to 999 going up to 20G, with the delays
+// shortening by section and a grade awarded from the score.
+pub struct MasterGame {
+    pub board: TetrisBoard,
+    pub level: u32,
+    pub score: u32,
+    combo: u32,
+    soft_drop_frames: u32,
+    pub frames: u32,
+    pub section_times: Vec<u32>,
+    section_start: u32,
+    is_gm_possible: bool,
+    pub is_complete: bool,
+}
+
+impl Default for MasterGame {
+    fn default() -> Self {
+        MasterGame::new()
+    }
+}
+
+impl MasterGame {
+    pub fn new() -> Self {
+        MasterGame::with_board(TetrisBoard::new())
+    }
+
+    pub fn with_seed(seed: usize) -> Self {
+        MasterGame::with_board(TetrisBoard::with_seed(seed))
+    }
+
+    fn with_board(board: TetrisBoard) -> Self {
+        MasterGame {
+            board: board,
+            level: 0,
+            score: 0,
+            combo: 1,
+            soft_drop_frames: 0,
+            frames: 0,
+            section_times: Vec::new(),
+            section_start: 0,
+            is_gm_possible: true,
+            is_complete: false,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.board.reset();
+        self.level = 0;
+        self.score = 0;
+        self.combo = 1;
+        self.soft_drop_frames = 0;
+        self.frames = 0;
+        self.section_times.clear();
+        self.section_start = 0;
+        self.is_gm_possible = true;
+        self.is_complete = false;
+    }
+
+    fn speed(&self) -> Speed {
+        let gravity = GRAVITY_CURVE.iter().rev()
+            .find(|&&(level, _)| level <= self.level)
+            .unwrap().1;
+        let (_, are, line_clear_delay, lock_delay) = *DELAY_CURVE.iter().rev()
+            .find(|&&(level, _, _, _)| level <= self.level)
+            .unwrap();
+        Speed {
+            gravity: gravity,
+            are: are,
+            line_clear_delay: line_clear_delay,
+            lock_delay: lock_delay,
+        }
+    }
+
+    pub fn section_goal(&self) -> u32 {
+        cmp::min((self.level / 100 + 1) * 100, MAX_LEVEL)
+    }
+
+    pub fn grade(&self) -> &'static str {
+        if self.is_complete && self.is_gm_possible {
+            return "GM";
+        }
+        GRADES.iter().rev()
+            .find(|&&(score, _)| score <= self.score)
+            .unwrap().1
+    }
+
+    fn advance_level(&mut self, levels: u32) {
+        let old_level = self.level;
+        self.level = cmp::min(self.level + levels, MAX_LEVEL);
+
+        if old_level / 100 != self.level / 100 || self.level == MAX_LEVEL {
+            self.section_times.push(self.frames - self.section_start);
+            self.section_start = self.frames;
+        }
+
+        for &(level, score, frames) in GM_CHECKPOINTS.iter() {
+            if old_level < level && self.level >= level && (self.score < score || self.frames > frames) {
+                self.is_gm_possible = false;
+            }
+        }
+
+        if self.level == MAX_LEVEL {
+            self.is_complete = true;
+        }
+    }
+
+    fn on_piece_locked(&mut self) {
+        let lines = self.board.last_clear_lines;
+        if lines > 0 {
+            self.combo += 2 * lines - 2;
+            let bravo = if self.board.is_perfect_clear() { 4 } else { 1 };
+            self.score += ((self.level + lines).div_ceil(4) + self.soft_drop_frames) * lines * self.combo * bravo;
+            self.advance_level(lines);
+        } else {
+            self.combo = 1;
+        }
+        self.soft_drop_frames = 0;
+
+        // The next piece's entry counts as a level, except at the end of a
+        // section where only line clears move on.
+        if !self.is_complete && self.level % 100 != 99 && self.level != MAX_LEVEL - 1 {
+            self.advance_level(1);
+        }
+    }
+
+    pub fn update(&mut self, input: &Input) {
+        if self.is_complete {
+            if input.up_key_pressed {
+                self.restart();
+            }
+            return;
+        }
+
+        let was_game_over = self.board.is_game_over;
+        let pieces_locked = self.board.pieces_locked;
+
+        if input.down_key_pressed && self.board.is_piece_active() {
+            self.soft_drop_frames += 1;
+        }
+        self.board.speed = Some(self.speed());
+        self.board.update(input);
+
+        if was_game_over {
+            if !self.board.is_game_over {
+                self.restart();
+            }
+            return;
+        }
+
+        if self.board.is_game_over {
+            return;
+        }
+
+        self.frames += 1;
+
+        if self.board.pieces_locked != pieces_locked {
+            self.on_piece_locked();
+        }
+    }
+}
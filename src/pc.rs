@@ -0,0 +1,342 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use ai::{self, Field};
+use pieces;
+use super::{start_pos, Input, PieceKind, TetrisBoard, TetrisPiece};
+
+// Perfect clears are looked for within this many lines.
+pub static MAX_LINES: usize = 4;
+// Pieces the practice mode shows and solves with, after the current one.
+pub static PREVIEW_SIZE: usize = 6;
+// Plenty to check the player's placements against, without searching every
+// order the same pieces can go in.
+static MAX_SOLUTIONS: usize = 200;
+// Fields to search before giving up, a few seconds' worth.
+static MAX_NODES: usize = 50000;
+
+// A placement in a solution, where it lands on the board as it is then.
+// Held pieces come out of hold, or the current piece goes in for the next.
+#[derive(Copy, Clone)]
+pub struct Step {
+    pub piece: TetrisPiece,
+    pub is_hold: bool,
+}
+
+// Empty cells in the bottom rows of the field, each group of them that
+// touch each other counted separately.
+fn empty_regions(field: &Field, rows: usize) -> Vec<usize> {
+    let top = field.height() - 1 - rows;
+    let mut seen = HashSet::new();
+    let mut regions = Vec::new();
+    for y in top..field.height()-1 {
+        for x in 1..field.width()-1 {
+            if field.is_filled(x, y) || !seen.insert((x, y)) {
+                continue;
+            }
+            let mut size = 0;
+            let mut queue = VecDeque::new();
+            queue.push_back((x, y));
+            while let Some((x, y)) = queue.pop_front() {
+                size += 1;
+                for &(nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter() {
+                    if ny >= top && !field.is_filled(nx, ny) && seen.insert((nx, ny)) {
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+            regions.push(size);
+        }
+    }
+    regions
+}
+
+fn is_clear_above(field: &Field, rows: usize) -> bool {
+    (1..field.height()-1-rows).all(|y| (1..field.width()-1).all(|x| !field.is_filled(x, y)))
+}
+
+struct Search<'a> {
+    // The current piece, then the preview.
+    pieces: &'a [PieceKind],
+    steps: Vec<Step>,
+    solutions: Vec<Vec<Step>>,
+    nodes: usize,
+    // Rows left to clear, the next piece and the held one, for fields that
+    // don't lead to a perfect clear.
+    dead_ends: HashSet<(Field, usize, usize, Option<PieceKind>)>,
+    // Whether nobody wants the answer any more.
+    is_cancelled: &'a dyn Fn() -> bool,
+}
+
+impl<'a> Search<'a> {
+    // The pieces that can go next: the next one, or the held one, or the one
+    // after if nothing is held yet. Each with the next piece and hold after.
+    fn choices(&self, next: usize, hold: Option<PieceKind>) -> Vec<(PieceKind, bool, usize, Option<PieceKind>)> {
+        let mut choices = Vec::new();
+        if let Some(&kind) = self.pieces.get(next) {
+            choices.push((kind, false, next + 1, hold));
+        }
+        match (hold, self.pieces.get(next)) {
+            (Some(held), Some(&kind)) if held != kind => choices.push((held, true, next + 1, Some(kind))),
+            // Whatever comes after the preview can go into hold.
+            (Some(held), None) => choices.push((held, true, next, None)),
+            (None, Some(&kind)) if next + 1 < self.pieces.len() => {
+                choices.push((self.pieces[next + 1], true, next + 2, Some(kind)))
+            }
+            _ => {}
+        }
+        choices
+    }
+
+    // Depth first over every placement that stays within the rows left, giving
+    // up on fields with empty cells that pieces can't exactly fill.
+    fn search(&mut self, field: &Field, rows: usize, next: usize, hold: Option<PieceKind>) -> bool {
+        self.nodes += 1;
+        if self.nodes > MAX_NODES || (self.is_cancelled)() {
+            return false;
+        }
+        let key = (field.clone(), rows, next, hold);
+        if self.dead_ends.contains(&key) {
+            return false;
+        }
+        let regions = empty_regions(field, rows);
+        let pieces_left = self.pieces.len() - next + hold.map_or(0, |_| 1);
+        if regions.iter().any(|size| size % 4 != 0) || regions.iter().sum::<usize>() > pieces_left * 4 {
+            self.dead_ends.insert(key);
+            return false;
+        }
+
+        let top = (field.height() - 1 - rows) as i32;
+        let mut is_solvable = false;
+        for (kind, is_hold, next, hold) in self.choices(next, hold) {
+            for (landing, _) in ai::find_placements(field, &TetrisPiece::spawn(kind, start_pos)) {
+                if landing.iter().any(|pos| pos.y < top) {
+                    continue;
+                }
+                let mut next_field = field.clone();
+                let lines = next_field.place(&landing) as usize;
+                self.steps.push(Step { piece: landing, is_hold: is_hold });
+                if lines == rows {
+                    self.solutions.push(self.steps.clone());
+                    is_solvable = true;
+                } else if self.search(&next_field, rows - lines, next, hold) {
+                    is_solvable = true;
+                }
+                self.steps.pop();
+                if self.solutions.len() >= MAX_SOLUTIONS {
+                    return true;
+                }
+            }
+        }
+        if !is_solvable {
+            self.dead_ends.insert(key);
+        }
+        is_solvable
+    }
+}
+
+// Ways to clear the whole field within MAX_LINES lines, playing the current
+// piece and then the queue, using hold. Lower perfect clears come first.
+pub fn solve(field: &Field, current: PieceKind, hold: Option<PieceKind>, queue: &[PieceKind]) -> Vec<Vec<Step>> {
+    solve_unless(field, current, hold, queue, &|| false)
+}
+
+fn solve_unless(field: &Field, current: PieceKind, hold: Option<PieceKind>, queue: &[PieceKind],
+                is_cancelled: &dyn Fn() -> bool) -> Vec<Vec<Step>> {
+    let mut pieces = vec![current];
+    pieces.extend_from_slice(queue);
+    let mut search = Search {
+        pieces: &pieces,
+        steps: Vec::new(),
+        solutions: Vec::new(),
+        nodes: 0,
+        dead_ends: HashSet::new(),
+        is_cancelled: is_cancelled,
+    };
+    for rows in 1..MAX_LINES + 1 {
+        if is_clear_above(field, rows) && search.solutions.len() < MAX_SOLUTIONS {
+            search.search(field, rows, 0, hold);
+        }
+    }
+    search.solutions
+}
+
+// A board for the worker to solve, numbered in the order they were asked for.
+struct Job {
+    field: Field,
+    current: PieceKind,
+    hold: Option<PieceKind>,
+    queue: Vec<PieceKind>,
+    generation: usize,
+}
+
+// Solves boards one after another until the game goes away. A search stops
+// as soon as a later board is asked for, and boards that were passed by while
+// it ran are skipped.
+fn work(jobs: Receiver<Job>, answers: Sender<(usize, Vec<Vec<Step>>)>, generation: Arc<AtomicUsize>) {
+    while let Ok(mut job) = jobs.recv() {
+        while let Ok(later) = jobs.try_recv() {
+            job = later;
+        }
+        let is_cancelled = || generation.load(Ordering::SeqCst) != job.generation;
+        if is_cancelled() {
+            continue;
+        }
+        let solutions = solve_unless(&job.field, job.current, job.hold, &job.queue, &is_cancelled);
+        if answers.send((job.generation, solutions)).is_err() {
+            break;
+        }
+    }
+}
+
+// Practice for perfect clears. Whenever the pieces in view can make one, the
+// player's placements are checked against every solution found, and the next
+// step of one still being followed can be shown as a hint. Solving can take a
+// while, so it happens on another thread while the game goes on.
+pub struct PcGame {
+    pub board: TetrisBoard,
+    // The solutions that agree with every piece placed since they were found.
+    pub solutions: Vec<Vec<Step>>,
+    step: usize,
+    pieces_locked: u32,
+    jobs: Sender<Job>,
+    answers: Receiver<(usize, Vec<Vec<Step>>)>,
+    // Boards asked about so far. Only the answer for the last one is wanted.
+    generation: Arc<AtomicUsize>,
+    is_solving: bool,
+    pub show_hints: bool,
+    pub perfect_clears: u32,
+    // Placements that went off every solution.
+    pub misses: u32,
+    pub is_missed: bool,
+}
+
+impl PcGame {
+    pub fn new() -> Result<Self, String> {
+        PcGame::with_board(TetrisBoard::new())
+    }
+
+    pub fn with_seed(seed: usize) -> Result<Self, String> {
+        PcGame::with_board(TetrisBoard::with_seed(seed))
+    }
+
+    // The search counts on every piece filling four cells.
+    fn with_board(board: TetrisBoard) -> Result<Self, String> {
+        pieces::require_tetrominoes("Perfect clear practice")?;
+        let (jobs, job_receiver) = mpsc::channel();
+        let (answer_sender, answers) = mpsc::channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+        let worker_generation = generation.clone();
+        thread::spawn(move || work(job_receiver, answer_sender, worker_generation));
+        let mut pc_game = PcGame {
+            board: board,
+            solutions: Vec::new(),
+            step: 0,
+            pieces_locked: 0,
+            jobs: jobs,
+            answers: answers,
+            generation: generation,
+            is_solving: false,
+            show_hints: true,
+            perfect_clears: 0,
+            misses: 0,
+            is_missed: false,
+        };
+        pc_game.solve();
+        Ok(pc_game)
+    }
+
+    // Starts looking for solutions from the board as it is now. Any search
+    // still going is for a board that has since changed, so it is called off.
+    fn solve(&mut self) {
+        let field = Field::from_board(&self.board);
+        let current = self.board.active_piece.kind;
+        let hold = self.board.hold_piece;
+        let queue: Vec<PieceKind> = self.board.tetris_gen.preview(PREVIEW_SIZE, start_pos).iter()
+            .map(|piece| piece.kind)
+            .collect();
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        // The worker only stops along with the game.
+        let _ = self.jobs.send(Job { field: field, current: current, hold: hold, queue: queue, generation: generation });
+        self.solutions.clear();
+        self.step = 0;
+        self.is_solving = true;
+    }
+
+    pub fn is_solving(&self) -> bool {
+        self.is_solving
+    }
+
+    // The next placement of the first solution still being followed.
+    pub fn hint(&self) -> Option<Step> {
+        self.solutions.first().and_then(|steps| steps.get(self.step).cloned())
+    }
+
+    fn check_placement(&mut self) {
+        let locked = match self.board.last_locked_piece {
+            Some(locked) => locked,
+            None => return,
+        };
+        let cells = ai::cells_key(&locked);
+        let step = self.step;
+        let was_solvable = !self.solutions.is_empty();
+        self.solutions.retain(|steps| {
+            steps.get(step).is_some_and(|s| s.piece.kind == locked.kind && ai::cells_key(&s.piece) == cells)
+        });
+        self.step += 1;
+        self.is_missed = was_solvable && self.solutions.is_empty();
+        if self.is_missed {
+            self.misses += 1;
+        }
+
+        if self.board.last_clear_lines > 0 && self.board.is_perfect_clear() {
+            self.perfect_clears += 1;
+            self.solutions.clear();
+        }
+        if self.solutions.is_empty() {
+            self.solve();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.board.reset();
+        self.pieces_locked = 0;
+        self.perfect_clears = 0;
+        self.misses = 0;
+        self.is_missed = false;
+        self.solve();
+    }
+
+    pub fn update(&mut self, input: &Input) {
+        while let Ok((generation, solutions)) = self.answers.try_recv() {
+            if generation == self.generation.load(Ordering::SeqCst) {
+                self.solutions = solutions;
+                self.is_solving = false;
+            }
+        }
+
+        self.board.update(input);
+
+        if self.board.pieces_locked < self.pieces_locked {
+            // The board started over after topping out.
+            self.perfect_clears = 0;
+            self.misses = 0;
+            self.is_missed = false;
+            self.solve();
+        } else if self.board.pieces_locked > self.pieces_locked {
+            self.check_placement();
+        }
+        self.pieces_locked = self.board.pieces_locked;
+    }
+}
+
+impl Drop for PcGame {
+    // Calls off the search still going. The worker finishes once it sees
+    // there are no more boards coming.
+    fn drop(&mut self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
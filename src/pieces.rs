@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::sync::OnceLock;
+
+use toml;
+
+use rotation::{self, RotationSystem};
+use super::{Color, PieceKind, Pos2D};
+
+// Enough for pentominoes, with room to spare.
+pub const MAX_PIECE_CELLS: usize = 8;
+
+// The pieces used when none are loaded.
+static STANDARD: &str = include_str!("../res/pieces/standard.toml");
+
+// A piece set file, in TOML. See res/pieces/standard.toml.
+#[derive(Deserialize)]
+struct PieceSetFile {
+    rotation_system: Option<String>,
+    #[serde(default)]
+    kicks: HashMap<String, KickTableFile>,
+    pieces: Vec<PieceFile>,
+}
+
+#[derive(Deserialize)]
+struct KickTableFile {
+    right: Vec<Vec<[i32; 2]>>,
+    left: Vec<Vec<[i32; 2]>>,
+}
+
+#[derive(Deserialize)]
+struct PieceFile {
+    letter: char,
+    color: [u8; 3],
+    #[serde(default)]
+    spawn: [i32; 2],
+    kicks: Option<String>,
+    rotations: Vec<Vec<[i32; 2]>>,
+}
+
+// The nudges to try, in order, for each rotation state and direction.
+#[derive(Clone)]
+pub struct KickTable {
+    pub right: Vec<Vec<Pos2D>>,
+    pub left: Vec<Vec<Pos2D>>,
+}
+
+impl KickTable {
+    fn from_file(file: &KickTableFile) -> Result<Self, String> {
+        if file.right.is_empty() || file.left.is_empty() {
+            return Err("Kick tables need kicks both ways".to_string());
+        }
+        let positions = |tests: &Vec<Vec<[i32; 2]>>| {
+            tests.iter().map(|kicks| kicks.iter().map(|&[x, y]| Pos2D::xy(x, y)).collect()).collect()
+        };
+        Ok(KickTable {
+            right: positions(&file.right),
+            left: positions(&file.left),
+        })
+    }
+
+    // Turns either way only where they are.
+    fn none() -> Self {
+        KickTable {
+            right: vec![vec![Pos2D::xy(0, 0)]],
+            left: vec![vec![Pos2D::xy(0, 0)]],
+        }
+    }
+
+    pub fn right_kicks(&self, orientation: usize) -> &[Pos2D] {
+        &self.right[orientation % self.right.len()]
+    }
+
+    pub fn left_kicks(&self, orientation: usize) -> &[Pos2D] {
+        &self.left[orientation % self.left.len()]
+    }
+}
+
+pub struct PieceDef {
+    pub kind: PieceKind,
+    pub color: Color,
+    pub spawn: Pos2D,
+    // Cells in each of the four rotation states; only the first `size` count.
+    pub shape: [[Pos2D; MAX_PIECE_CELLS]; 4],
+    pub size: usize,
+    pub kicks: KickTable,
+}
+
+impl PieceDef {
+    fn from_file(file: &PieceFile, kick_tables: &HashMap<String, KickTable>) -> Result<Self, String> {
+        let kicks = match file.kicks {
+            Some(ref name) => kick_tables.get(name).cloned()
+                .ok_or_else(|| format!("Piece {} uses kicks {}, which aren't defined", file.letter, name))?,
+            None => KickTable::none(),
+        };
+        let size = file.rotations.first().map_or(0, |cells| cells.len());
+        if file.rotations.is_empty() || file.rotations.len() > 4 {
+            return Err(format!("Piece {} needs one to four rotation states", file.letter));
+        }
+        if size == 0 || size > MAX_PIECE_CELLS || file.rotations.iter().any(|cells| cells.len() != size) {
+            return Err(format!("Piece {} needs the same 1 to {} cells in every rotation state", file.letter, MAX_PIECE_CELLS));
+        }
+
+        let mut shape = [[Pos2D::xy(0, 0); MAX_PIECE_CELLS]; 4];
+        for (orientation, cells) in shape.iter_mut().enumerate() {
+            for (cell, &[x, y]) in cells.iter_mut().zip(file.rotations[orientation % file.rotations.len()].iter()) {
+                *cell = Pos2D::xy(x, y);
+            }
+        }
+        Ok(PieceDef {
+            kind: PieceKind(file.letter),
+            color: Color::RGB(file.color[0], file.color[1], file.color[2]),
+            spawn: Pos2D::xy(file.spawn[0], file.spawn[1]),
+            shape: shape,
+            size: size,
+            kicks: kicks,
+        })
+    }
+}
+
+// The pieces the game is played with, and how they turn. The bag deals one
+// of each.
+pub struct PieceSet {
+    pub pieces: Vec<PieceDef>,
+    pub rotation_system: Box<dyn RotationSystem + Send + Sync>,
+}
+
+impl PieceSet {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let file: PieceSetFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let name = file.rotation_system.as_ref().map_or("srs", |name| name.as_str());
+        let rotation_system = rotation::from_name(name)
+            .ok_or_else(|| format!("Unknown rotation system {}, expected srs or ars", name))?;
+        let mut kick_tables = HashMap::new();
+        for (name, table) in file.kicks.iter() {
+            kick_tables.insert(name.clone(), KickTable::from_file(table)?);
+        }
+        let pieces = file.pieces.iter()
+            .map(|piece| PieceDef::from_file(piece, &kick_tables))
+            .collect::<Result<Vec<PieceDef>, String>>()?;
+        if pieces.is_empty() {
+            return Err("A piece set needs some pieces".to_string());
+        }
+        for (i, piece) in pieces.iter().enumerate() {
+            if pieces[..i].iter().any(|other| other.kind == piece.kind) {
+                return Err(format!("There is more than one piece {}", piece.kind.letter()));
+            }
+        }
+        Ok(PieceSet {
+            pieces: pieces,
+            rotation_system: rotation_system,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        PieceSet::from_toml(&text).map_err(|e| format!("Failed to read {}: {}", path, e))
+    }
+
+    pub fn standard() -> Self {
+        PieceSet::from_toml(STANDARD).unwrap()
+    }
+
+    pub fn get(&self, kind: PieceKind) -> Option<&PieceDef> {
+        self.pieces.iter().find(|piece| piece.kind == kind)
+    }
+
+    // The seven tetrominoes and nothing else, whatever their shapes, colors
+    // and kicks.
+    pub fn is_tetrominoes(&self) -> bool {
+        self.pieces.len() == 7 && "IOTSZJL".chars().all(|letter| self.get(PieceKind(letter)).is_some_and(|piece| piece.size == 4))
+    }
+}
+
+static PIECE_SET: OnceLock<PieceSet> = OnceLock::new();
+
+pub fn piece_set() -> &'static PieceSet {
+    PIECE_SET.get_or_init(PieceSet::standard)
+}
+
+// For TBP, fumen and puzzles, which only know the seven tetrominoes, so
+// other pieces with the same letters aren't taken for them.
+pub fn require_tetrominoes(what: &str) -> Result<(), String> {
+    if piece_set().is_tetrominoes() {
+        Ok(())
+    } else {
+        Err(format!("{} only work with the seven tetrominoes", what))
+    }
+}
+
+// Plays with these pieces instead of the standard ones. Only works at
+// startup, before any piece has been made.
+pub fn use_piece_set(set: PieceSet) -> Result<(), String> {
+    PIECE_SET.set(set).map_err(|_| "The pieces are already in use".to_string())
+}
@@ -0,0 +1,175 @@
+use std::fs::File;
+use std::io::Read;
+
+use toml;
+
+use fumen;
+use pieces;
+use super::{start_pos, Color, Input, PieceKind, RandomTetrisPieceGenerator, TetrisBoard, TetrisPiece, TetrisUnitBlock};
+
+#[derive(Deserialize, Copy, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    ClearLines { lines: u32 },
+    TSpinTriple,
+    PerfectClear,
+}
+
+impl Goal {
+    pub fn description(&self) -> String {
+        match *self {
+            Goal::ClearLines { lines } => format!("Clear {} lines", lines),
+            Goal::TSpinTriple => "T-spin triple".to_string(),
+            Goal::PerfectClear => "Perfect clear".to_string(),
+        }
+    }
+}
+
+// A puzzle file, in TOML. The board is either drawn as rows of text or
+// given as a fumen.
+#[derive(Deserialize)]
+struct PuzzleFile {
+    name: Option<String>,
+    board: Option<String>,
+    fumen: Option<String>,
+    // Piece letters, in the order they are dealt.
+    pieces: String,
+    goal: Goal,
+}
+
+pub struct Puzzle {
+    pub name: String,
+    // Rows top to bottom, sitting on the floor.
+    stack: Vec<Vec<TetrisUnitBlock>>,
+    pub pieces: Vec<PieceKind>,
+    pub goal: Goal,
+}
+
+// Rows of the board drawn as text, one character per cell: . for empty, G
+// for garbage, or a piece letter for a block of that piece's color.
+fn parse_rows(text: &str) -> Result<Vec<Vec<TetrisUnitBlock>>, String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if line.chars().count() != 10 {
+                return Err(format!("Board rows need 10 cells: {}", line));
+            }
+            line.chars().map(|c| {
+                let letter = c.to_string();
+                match c {
+                    '.' => Ok(TetrisUnitBlock { is_filled: false, color: Color::RGB(0, 0, 0), is_garbage: false, kind: None }),
+                    'G' => Ok(TetrisUnitBlock { is_filled: true, color: Color::RGB(128, 128, 128), is_garbage: true, kind: None }),
+                    _ => PieceKind::from_letter(&letter)
+                        .map(|kind| TetrisUnitBlock { is_filled: true, color: TetrisPiece::build(kind, start_pos).color, is_garbage: false, kind: Some(kind) })
+                        .ok_or_else(|| format!("Unknown cell {} in the board", c)),
+                }
+            }).collect()
+        })
+        .collect()
+}
+
+impl Puzzle {
+    pub fn load(path: &str) -> Result<Self, String> {
+        pieces::require_tetrominoes("Puzzles")?;
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let file: PuzzleFile = toml::from_str(&text).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+        let stack = match (file.board, file.fumen) {
+            (Some(board), _) => parse_rows(&board)?,
+            (None, Some(data)) => fumen::decode(&data)?.first().map_or_else(Vec::new, |page| page.stack()),
+            (None, None) => Vec::new(),
+        };
+        let pieces = file.pieces.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| PieceKind::from_letter(&c.to_string()).ok_or_else(|| format!("Unknown piece {}", c)))
+            .collect::<Result<Vec<PieceKind>, String>>()?;
+        if pieces.is_empty() {
+            return Err("A puzzle needs some pieces".to_string());
+        }
+
+        Ok(Puzzle {
+            name: file.name.unwrap_or_else(|| path.to_string()),
+            stack: stack,
+            pieces: pieces,
+            goal: file.goal,
+        })
+    }
+
+    pub fn board(&self) -> TetrisBoard {
+        TetrisBoard::with_setup(&self.stack, RandomTetrisPieceGenerator::with_sequence(&self.pieces))
+    }
+}
+
+// Practice mode: reach the puzzle's goal with the pieces it gives. Up
+// starts over once it is solved or failed.
+pub struct PuzzleGame {
+    pub puzzle: Puzzle,
+    pub board: TetrisBoard,
+    pub attempts: u32,
+    pub is_solved: bool,
+    pub is_failed: bool,
+}
+
+impl PuzzleGame {
+    pub fn new(puzzle: Puzzle) -> Self {
+        PuzzleGame {
+            board: puzzle.board(),
+            puzzle: puzzle,
+            attempts: 1,
+            is_solved: false,
+            is_failed: false,
+        }
+    }
+
+    pub fn retry(&mut self) {
+        self.board = self.puzzle.board();
+        self.attempts += 1;
+        self.is_solved = false;
+        self.is_failed = false;
+    }
+
+    pub fn pieces_left(&self) -> usize {
+        self.puzzle.pieces.len().saturating_sub(self.board.pieces_locked as usize)
+    }
+
+    fn is_goal_reached(&self) -> bool {
+        match self.puzzle.goal {
+            Goal::ClearLines { lines } => self.board.lines_cleared >= lines,
+            Goal::TSpinTriple => self.board.last_lock_was_t_spin && self.board.last_clear_lines == 3,
+            Goal::PerfectClear => self.board.last_clear_lines > 0 && self.board.is_perfect_clear(),
+        }
+    }
+
+    pub fn update(&mut self, input: &Input) {
+        if self.is_solved || self.is_failed {
+            if input.up_key_pressed {
+                self.retry();
+            }
+            return;
+        }
+
+        // Don't let the board start itself over with random pieces.
+        if self.board.is_game_over {
+            self.is_failed = true;
+            return;
+        }
+
+        let pieces_locked = self.board.pieces_locked;
+        self.board.update(input);
+
+        if self.board.pieces_locked != pieces_locked {
+            if self.is_goal_reached() {
+                self.is_solved = true;
+            } else if self.pieces_left() == 0 {
+                self.is_failed = true;
+            }
+        }
+        if self.board.is_game_over && !self.is_solved {
+            self.is_failed = true;
+        }
+    }
+}
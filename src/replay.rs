@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use serde_json;
+
+use super::{Controller, Input, TetrisBoard};
+
+// Every frame's input for one game. Played back on the same ruleset and seed
+// it gives the same game.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub ruleset: String,
+    pub seed: usize,
+    // Input::to_bits for each frame.
+    pub inputs: Vec<u16>,
+}
+
+impl Replay {
+    pub fn new(ruleset: &str, seed: usize) -> Self {
+        Replay {
+            ruleset: ruleset.to_string(),
+            seed: seed,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, input: &Input) {
+        self.inputs.push(input.to_bits());
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut json = String::new();
+        File::open(path)?.read_to_string(&mut json)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        File::create(path)?.write_all(serde_json::to_string(self).unwrap().as_bytes())
+    }
+}
+
+// Presses the keys from a replay, then nothing once it runs out.
+pub struct ReplayPlayer {
+    inputs: Vec<u16>,
+    frame: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: &Replay) -> Self {
+        ReplayPlayer {
+            inputs: replay.inputs.clone(),
+            frame: 0,
+        }
+    }
+}
+
+impl Controller for ReplayPlayer {
+    fn name(&self) -> &str {
+        "Replay"
+    }
+
+    fn next_input(&mut self, _board: &TetrisBoard) -> Input {
+        let bits = self.inputs.get(self.frame).cloned().unwrap_or(0);
+        self.frame += 1;
+        Input::from_bits(bits)
+    }
+}
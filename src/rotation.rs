@@ -0,0 +1,95 @@
+use pieces::piece_set;
+use super::{PieceKind, Pos2D, TetrisPiece};
+
+// How pieces turn, and where they go when the turn is blocked. `fits` says
+// whether a piece is clear of the walls and the stack.
+pub trait RotationSystem {
+    fn name(&self) -> &str;
+    fn rotate(&self, piece: &TetrisPiece, clockwise: bool, fits: &dyn Fn(&TetrisPiece) -> bool) -> Option<TetrisPiece>;
+}
+
+pub fn from_name(name: &str) -> Option<Box<dyn RotationSystem + Send + Sync>> {
+    match name {
+        "srs" => Some(Box::new(Srs)),
+        "ars" => Some(Box::new(Ars)),
+        _ => None,
+    }
+}
+
+fn turned(piece: &TetrisPiece, clockwise: bool) -> TetrisPiece {
+    let mut rotated = *piece;
+    if clockwise {
+        rotated.rotate_right();
+    } else {
+        rotated.rotate_left();
+    }
+    rotated
+}
+
+fn moved(piece: &TetrisPiece, by: Pos2D) -> TetrisPiece {
+    let mut moved = *piece;
+    moved.move_by(by);
+    moved
+}
+
+// The Super Rotation System, as in the guideline games: the first of the
+// nudges in the piece's kick table that fits.
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn name(&self) -> &str {
+        "SRS"
+    }
+
+    fn rotate(&self, piece: &TetrisPiece, clockwise: bool, fits: &dyn Fn(&TetrisPiece) -> bool) -> Option<TetrisPiece> {
+        let rotated = turned(piece, clockwise);
+        let kicks = &piece_set().get(piece.kind).unwrap().kicks;
+        let tests = if clockwise { kicks.right_kicks(piece.orientation) } else { kicks.left_kicks(piece.orientation) };
+        tests.iter()
+            .map(|&kick| moved(&rotated, kick))
+            .find(|kicked| fits(kicked))
+    }
+}
+
+// The Arika Rotation System, as in TGM. A blocked turn is tried one column
+// right, then one left, except for the I, which never kicks, and the L, J
+// and T when the first blocked cell, in reading order, is in their center
+// column, so they can't climb over the stack.
+pub struct Ars;
+
+impl Ars {
+    fn is_blocked_in_center(rotated: &TetrisPiece, fits: &dyn Fn(&TetrisPiece) -> bool) -> bool {
+        let mut cells: Vec<Pos2D> = rotated.blocks().to_vec();
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        // A piece of just the one cell, to see if that cell is blocked.
+        let mut probe = *rotated;
+        probe.size = 1;
+        cells.iter()
+            .find(|&&cell| {
+                probe.shape[probe.orientation][0] = cell;
+                !fits(&probe)
+            })
+            .is_some_and(|cell| cell.x == 0)
+    }
+}
+
+impl RotationSystem for Ars {
+    fn name(&self) -> &str {
+        "ARS"
+    }
+
+    fn rotate(&self, piece: &TetrisPiece, clockwise: bool, fits: &dyn Fn(&TetrisPiece) -> bool) -> Option<TetrisPiece> {
+        let rotated = turned(piece, clockwise);
+        if fits(&rotated) {
+            return Some(rotated);
+        }
+        match piece.kind {
+            PieceKind::I => return None,
+            PieceKind::L | PieceKind::J | PieceKind::T if Ars::is_blocked_in_center(&rotated, fits) => return None,
+            _ => {}
+        }
+        [Pos2D::xy(1, 0), Pos2D::xy(-1, 0)].iter()
+            .map(|&kick| moved(&rotated, kick))
+            .find(|kicked| fits(kicked))
+    }
+}
@@ -0,0 +1,93 @@
+use dig::DigGame;
+use master::MasterGame;
+use replay::Replay;
+use super::{Controller, Input, TetrisBoard};
+
+pub static RULESETS: [&str; 3] = ["marathon", "dig", "master"];
+
+// The single player modes, built from a seed so games can be repeated.
+// There's only one at a time, so it isn't worth boxing the bigger ones.
+#[allow(clippy::large_enum_variant)]
+pub enum Ruleset {
+    Marathon(TetrisBoard),
+    Dig(DigGame),
+    Master(MasterGame),
+}
+
+impl Ruleset {
+    // Dig uses the same defaults as the game.
+    pub fn new(name: &str, seed: usize) -> Option<Self> {
+        match name {
+            "marathon" => Some(Ruleset::Marathon(TetrisBoard::with_seed(seed))),
+            "dig" => Some(Ruleset::Dig(DigGame::with_seed(18, 10, 0.3, seed))),
+            "master" => Some(Ruleset::Master(MasterGame::with_seed(seed))),
+            _ => None,
+        }
+    }
+
+    pub fn board(&self) -> &TetrisBoard {
+        match *self {
+            Ruleset::Marathon(ref board) => board,
+            Ruleset::Dig(ref dig_game) => &dig_game.board,
+            Ruleset::Master(ref master_game) => &master_game.board,
+        }
+    }
+
+    pub fn score(&self) -> u32 {
+        match *self {
+            Ruleset::Master(ref master_game) => master_game.score,
+            _ => self.board().score,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match *self {
+            Ruleset::Marathon(_) => false,
+            Ruleset::Dig(ref dig_game) => dig_game.is_complete,
+            Ruleset::Master(ref master_game) => master_game.is_complete,
+        }
+    }
+
+    pub fn update(&mut self, input: &Input) {
+        match *self {
+            Ruleset::Marathon(ref mut board) => board.update(input),
+            Ruleset::Dig(ref mut dig_game) => dig_game.update(input),
+            Ruleset::Master(ref mut master_game) => master_game.update(input),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct GameStats {
+    pub seed: usize,
+    pub lines: u32,
+    pub score: u32,
+    pub pieces: u32,
+    // Frames survived, at 60 a second.
+    pub frames: u32,
+    pub is_complete: bool,
+    pub top_out: Option<String>,
+}
+
+// Plays a game frame by frame with no waiting in between, until it ends or
+// runs out of time, recording the inputs into the replay.
+pub fn run(mut game: Ruleset, controller: &mut dyn Controller, max_frames: u32, replay: &mut Replay) -> GameStats {
+    let mut frames = 0;
+    while frames < max_frames && !game.board().is_game_over && !game.is_complete() {
+        let input = controller.next_input(game.board());
+        replay.record(&input);
+        game.update(&input);
+        frames += 1;
+    }
+
+    let board = game.board();
+    GameStats {
+        seed: replay.seed,
+        lines: board.lines_cleared,
+        score: game.score(),
+        pieces: board.pieces_locked,
+        frames: frames,
+        is_complete: game.is_complete(),
+        top_out: board.top_out.map(|top_out| top_out.name().to_string()),
+    }
+}
@@ -0,0 +1,295 @@
+use std::cmp;
+
+use game::Game;
+use super::{PieceKind, Pos2D, TetrisBoard};
+
+// Sounds are made here rather than read from files, as 16 bit mono samples.
+pub static SAMPLE_RATE: u32 = 22050;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    Hold,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    // Any T-spin that clears lines.
+    TSpin,
+    LevelUp,
+    GameOver,
+}
+
+pub static SOUNDS: [Sound; 11] = [Sound::Move, Sound::Rotate, Sound::Lock, Sound::Hold, Sound::Single, Sound::Double,
+                                  Sound::Triple, Sound::Tetris, Sound::TSpin, Sound::LevelUp, Sound::GameOver];
+
+// From 0 to 100. The master volume turns down the music and the sound
+// effects both.
+#[derive(Copy, Clone)]
+pub struct Volume {
+    pub master: u32,
+    pub music: u32,
+    pub effects: u32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume::new()
+    }
+}
+
+impl Volume {
+    pub fn new() -> Self {
+        Volume {
+            master: 100,
+            music: 60,
+            effects: 100,
+        }
+    }
+
+    // Out of `max`, the loudest the mixer goes.
+    pub fn music_level(&self, max: u32) -> u32 {
+        cmp::min(self.master, 100) * cmp::min(self.music, 100) * max / 10000
+    }
+
+    pub fn effects_level(&self, max: u32) -> u32 {
+        cmp::min(self.master, 100) * cmp::min(self.effects, 100) * max / 10000
+    }
+}
+
+// A board's piece and state as of the last update, to tell what happened.
+struct BoardSounds {
+    piece: Option<(PieceKind, Pos2D, usize)>,
+    pieces_locked: u32,
+    is_hold_used: bool,
+    is_game_over: bool,
+}
+
+impl BoardSounds {
+    fn new(board: &TetrisBoard) -> Self {
+        BoardSounds {
+            piece: BoardSounds::active_piece(board),
+            pieces_locked: board.pieces_locked,
+            is_hold_used: board.is_hold_used,
+            is_game_over: board.is_game_over,
+        }
+    }
+
+    fn active_piece(board: &TetrisBoard) -> Option<(PieceKind, Pos2D, usize)> {
+        if board.is_piece_active() && !board.is_game_over {
+            let piece = &board.active_piece;
+            Some((piece.kind, piece.pos, piece.orientation))
+        } else {
+            None
+        }
+    }
+
+    fn update(&mut self, board: &TetrisBoard, sounds: &mut Vec<Sound>) {
+        // The board started over.
+        if board.pieces_locked < self.pieces_locked {
+            *self = BoardSounds::new(board);
+            return;
+        }
+
+        let piece = BoardSounds::active_piece(board);
+        let is_new_piece = board.pieces_locked != self.pieces_locked || board.is_hold_used != self.is_hold_used;
+        if board.pieces_locked != self.pieces_locked {
+            sounds.push(Sound::Lock);
+            let lines = board.last_clear_lines;
+            if lines > 0 {
+                sounds.push(match lines {
+                    _ if board.last_lock_was_t_spin => Sound::TSpin,
+                    1 => Sound::Single,
+                    2 => Sound::Double,
+                    3 => Sound::Triple,
+                    _ => Sound::Tetris,
+                });
+            }
+        }
+        if board.is_hold_used && !self.is_hold_used {
+            sounds.push(Sound::Hold);
+        }
+        if let (false, Some((kind, pos, orientation)), Some((old_kind, old_pos, old_orientation))) = (is_new_piece, piece, self.piece) {
+            // Kicks move the piece too, but only sound like a rotation.
+            if kind == old_kind && orientation != old_orientation {
+                sounds.push(Sound::Rotate);
+            } else if kind == old_kind && pos.x != old_pos.x {
+                sounds.push(Sound::Move);
+            }
+        }
+        if board.is_game_over && !self.is_game_over {
+            sounds.push(Sound::GameOver);
+        }
+
+        self.piece = piece;
+        self.pieces_locked = board.pieces_locked;
+        self.is_hold_used = board.is_hold_used;
+        self.is_game_over = board.is_game_over;
+    }
+}
+
+// Tells which sounds to play for what happened in a game, to the boards of
+// the players at the keyboard.
+pub struct SoundTracker {
+    boards: Vec<BoardSounds>,
+    level: Option<u32>,
+}
+
+impl Default for SoundTracker {
+    fn default() -> Self {
+        SoundTracker::new()
+    }
+}
+
+impl SoundTracker {
+    pub fn new() -> Self {
+        SoundTracker {
+            boards: Vec::new(),
+            level: None,
+        }
+    }
+
+    // Called after each update of the game.
+    pub fn update(&mut self, game: &Game) -> Vec<Sound> {
+        let boards: Vec<&TetrisBoard> = match *game {
+            Game::Versus(ref versus_game, None) => versus_game.players.iter().map(|player| &player.board).collect(),
+            Game::Versus(ref versus_game, Some(_)) => vec![&versus_game.players[0].board],
+            _ => game.board().into_iter().collect(),
+        };
+        let mut sounds = Vec::new();
+        if self.boards.len() != boards.len() {
+            self.boards = boards.iter().map(|board| BoardSounds::new(board)).collect();
+        } else {
+            for (board_sounds, board) in self.boards.iter_mut().zip(boards.iter()) {
+                board_sounds.update(board, &mut sounds);
+            }
+        }
+
+        let level = game.level();
+        if let (Some(before), Some(after)) = (self.level, level) {
+            if after > before {
+                sounds.push(Sound::LevelUp);
+            }
+        }
+        self.level = level;
+        sounds
+    }
+}
+
+// How fast the music plays, speeding up with the level.
+pub fn music_tempo(game: &Game) -> f32 {
+    let steps = match *game {
+        Game::Master(ref master_game) => master_game.level / 100,
+        _ => game.level().map_or(0, |level| level.saturating_sub(1)),
+    };
+    1.0 + cmp::min(steps, 10) as f32 * 0.06
+}
+
+// MIDI note numbers, 69 being the A at 440 Hz.
+fn frequency(note: u32) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+// A square wave note that dies away, added into `samples` from `start`.
+fn tone(samples: &mut Vec<i16>, start: usize, note: u32, seconds: f32, volume: f32) {
+    let length = (seconds * SAMPLE_RATE as f32) as usize;
+    if samples.len() < start + length {
+        samples.resize(start + length, 0);
+    }
+    let period = SAMPLE_RATE as f32 / frequency(note);
+    for i in 0..length {
+        let wave = if (i as f32 % period) < period / 2.0 { 1.0 } else { -1.0 };
+        // A quick fade in and a slower fade out, so notes don't click.
+        let envelope = (i as f32 / 100.0).min(1.0) * (1.0 - i as f32 / length as f32);
+        let sample = samples[start + i] as f32 + wave * envelope * volume * i16::MAX as f32;
+        samples[start + i] = sample.max(i16::MIN as f32).min(i16::MAX as f32) as i16;
+    }
+}
+
+// Notes one after another, each `seconds` long.
+fn arpeggio(notes: &[u32], seconds: f32, volume: f32) -> Vec<i16> {
+    let mut samples = Vec::new();
+    let step = (seconds * SAMPLE_RATE as f32) as usize;
+    for (i, &note) in notes.iter().enumerate() {
+        // The last note rings on.
+        let length = if i + 1 == notes.len() { seconds * 3.0 } else { seconds };
+        tone(&mut samples, i * step, note, length, volume);
+    }
+    samples
+}
+
+pub fn effect_samples(sound: Sound) -> Vec<i16> {
+    match sound {
+        Sound::Move => arpeggio(&[81], 0.03, 0.15),
+        Sound::Rotate => arpeggio(&[88], 0.04, 0.15),
+        Sound::Lock => arpeggio(&[45], 0.06, 0.3),
+        Sound::Hold => arpeggio(&[76, 83], 0.05, 0.2),
+        Sound::Single => arpeggio(&[72, 76], 0.06, 0.25),
+        Sound::Double => arpeggio(&[72, 76, 79], 0.06, 0.25),
+        Sound::Triple => arpeggio(&[72, 76, 79, 84], 0.06, 0.25),
+        Sound::Tetris => arpeggio(&[72, 76, 79, 84, 88, 91], 0.06, 0.3),
+        Sound::TSpin => arpeggio(&[69, 74, 77, 81, 86], 0.05, 0.3),
+        Sound::LevelUp => arpeggio(&[79, 84, 88, 91, 96], 0.05, 0.25),
+        Sound::GameOver => arpeggio(&[72, 67, 64, 60, 48], 0.18, 0.3),
+    }
+}
+
+// Korobeiniki, as (note, beats), with 0 for a rest, and a bass note every
+// half bar.
+static MELODY: [(u32, f32); 38] = [
+    (76, 1.0), (71, 0.5), (72, 0.5), (74, 1.0), (72, 0.5), (71, 0.5),
+    (69, 1.0), (69, 0.5), (72, 0.5), (76, 1.0), (74, 0.5), (72, 0.5),
+    (71, 1.5), (72, 0.5), (74, 1.0), (76, 1.0),
+    (72, 1.0), (69, 1.0), (69, 2.0),
+    (74, 1.5), (77, 0.5), (81, 1.0), (79, 0.5), (77, 0.5),
+    (76, 1.5), (72, 0.5), (76, 1.0), (74, 0.5), (72, 0.5),
+    (71, 1.0), (71, 0.5), (72, 0.5), (74, 1.0), (76, 1.0),
+    (72, 1.0), (69, 1.0), (69, 1.0), (0, 1.0),
+];
+static BASS: [u32; 16] = [40, 40, 45, 45, 44, 44, 45, 45, 38, 38, 36, 36, 44, 40, 45, 45];
+static BEATS_PER_MINUTE: f32 = 140.0;
+
+// The tune once through, to loop, sped up by `tempo`.
+pub fn music_samples(tempo: f32) -> Vec<i16> {
+    let beat = 60.0 / BEATS_PER_MINUTE / tempo;
+    let at = |beats: f32| (beats * beat * SAMPLE_RATE as f32) as usize;
+    let mut samples = Vec::new();
+    let mut beats = 0.0;
+    for &(note, length) in MELODY.iter() {
+        if note > 0 {
+            tone(&mut samples, at(beats), note, length * beat, 0.12);
+        }
+        beats += length;
+    }
+    for (i, &note) in BASS.iter().enumerate() {
+        tone(&mut samples, at(i as f32 * 2.0), note, beat * 2.0, 0.08);
+    }
+    // Exactly the tune's length, so it loops in time.
+    samples.resize(at(beats), 0);
+    samples
+}
+
+// A WAV file of the samples, for SDL_mixer to load.
+pub fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    let push_u32 = |bytes: &mut Vec<u8>, n: u32| bytes.extend_from_slice(&[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]);
+    bytes.extend_from_slice(b"RIFF");
+    push_u32(&mut bytes, 36 + data_size);
+    bytes.extend_from_slice(b"WAVEfmt ");
+    push_u32(&mut bytes, 16);
+    // PCM, one channel.
+    bytes.extend_from_slice(&[1, 0, 1, 0]);
+    push_u32(&mut bytes, SAMPLE_RATE);
+    push_u32(&mut bytes, SAMPLE_RATE * 2);
+    // Two bytes a frame, 16 bits a sample.
+    bytes.extend_from_slice(&[2, 0, 16, 0]);
+    bytes.extend_from_slice(b"data");
+    push_u32(&mut bytes, data_size);
+    for &sample in samples {
+        bytes.extend_from_slice(&[sample as u8, (sample >> 8) as u8]);
+    }
+    bytes
+}
@@ -0,0 +1,499 @@
+// The Tetris Bot Protocol: JSON messages, one per line, between a frontend
+// running the game and a bot suggesting where to put pieces.
+// https://github.com/tetris-bot-protocol/tbp-spec
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json;
+
+use ai::{self, Field, Move, Placement};
+use pieces;
+use super::{start_pos, tetris_board_height, tetris_board_width, Controller, Input, PieceKind, Pos2D, TetrisBoard, TetrisPiece};
+
+static ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+// Rows in a TBP board, which is taller than ours.
+static BOARD_ROWS: usize = 40;
+
+static PREVIEW_SIZE: usize = 5;
+// How long a bot gets to quit before it's killed.
+static QUIT_TIMEOUT_MS: u64 = 500;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules {
+        #[serde(default)]
+        randomizer: String,
+    },
+    Start {
+        hold: Option<String>,
+        queue: Vec<String>,
+        combo: u32,
+        back_to_back: bool,
+        // Bottom row first, each cell a piece letter, "G" for garbage, or null.
+        board: Vec<Vec<Option<String>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        piece_move: TbpMove,
+    },
+    NewPiece {
+        piece: String,
+    },
+    Quit,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Ready,
+    Suggestion {
+        moves: Vec<TbpMove>,
+    },
+    Error {
+        reason: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TbpMove {
+    pub location: PieceLocation,
+    // "none", "mini" or "full".
+    pub spin: String,
+}
+
+// A piece by the cell it rotates around, with x going right from the left
+// wall and y going up from the floor.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub orientation: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+// TBP only has the seven tetrominoes.
+fn north_cells(kind: PieceKind) -> Option<[(i32, i32); 4]> {
+    match kind {
+        PieceKind::I => Some([(-1, 0), (0, 0), (1, 0), (2, 0)]),
+        PieceKind::O => Some([(0, 0), (1, 0), (0, 1), (1, 1)]),
+        PieceKind::T => Some([(-1, 0), (0, 0), (1, 0), (0, 1)]),
+        PieceKind::S => Some([(-1, 0), (0, 0), (0, 1), (1, 1)]),
+        PieceKind::Z => Some([(-1, 1), (0, 1), (0, 0), (1, 0)]),
+        PieceKind::J => Some([(-1, 1), (-1, 0), (0, 0), (1, 0)]),
+        PieceKind::L => Some([(-1, 0), (0, 0), (1, 0), (1, 1)]),
+        _ => None,
+    }
+}
+
+// Cells relative to the piece's location, each turn rotating clockwise.
+fn tbp_cells(kind: PieceKind, orientation: usize) -> Option<Vec<(i32, i32)>> {
+    north_cells(kind).map(|cells| cells.iter().map(|&(x, y)| {
+        let mut cell = (x, y);
+        for _ in 0..orientation {
+            cell = (cell.1, -cell.0);
+        }
+        cell
+    }).collect())
+}
+
+fn to_board(x: i32, y: i32) -> (i32, i32) {
+    (x + 1, tetris_board_height as i32 - 2 - y)
+}
+
+fn from_board(x: i32, y: i32) -> (i32, i32) {
+    (x - 1, tetris_board_height as i32 - 2 - y)
+}
+
+// The offset that moves `shape` onto `cells`, if they are the same shape.
+fn align(shape: &[(i32, i32)], cells: &[(i32, i32)]) -> Option<(i32, i32)> {
+    let mut shape = shape.to_vec();
+    let mut cells = cells.to_vec();
+    shape.sort();
+    cells.sort();
+    let offset = (cells[0].0 - shape[0].0, cells[0].1 - shape[0].1);
+    if shape.iter().zip(cells.iter()).all(|(s, c)| s.0 + offset.0 == c.0 && s.1 + offset.1 == c.1) {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+pub fn location_to_piece(location: &PieceLocation) -> Option<TetrisPiece> {
+    let kind = PieceKind::from_letter(&location.kind)?;
+    let orientation = ORIENTATIONS.iter().position(|&o| o == location.orientation)?;
+    let cells: Vec<(i32, i32)> = tbp_cells(kind, orientation)?.iter()
+        .map(|&(x, y)| to_board(location.x + x, location.y + y))
+        .collect();
+
+    // Prefer our orientation with the same number, so spins still line up.
+    let mut piece = TetrisPiece::build(kind, Pos2D::xy(0, 0));
+    for i in 0..4 {
+        piece.orientation = (orientation + i) % 4;
+        let shape: Vec<(i32, i32)> = piece.blocks().iter().map(|pos| (pos.x, pos.y)).collect();
+        if let Some((x, y)) = align(&shape, &cells) {
+            piece.pos = Pos2D::xy(x, y);
+            return Some(piece);
+        }
+    }
+    None
+}
+
+pub fn piece_to_location(piece: &TetrisPiece) -> Option<PieceLocation> {
+    let cells: Vec<(i32, i32)> = piece.iter().map(|pos| from_board(pos.x, pos.y)).collect();
+    for i in 0..4 {
+        let orientation = (piece.orientation + i) % 4;
+        if let Some((x, y)) = align(&tbp_cells(piece.kind, orientation)?, &cells) {
+            return Some(PieceLocation {
+                kind: piece.kind.letter().to_string(),
+                orientation: ORIENTATIONS[orientation].to_string(),
+                x: x,
+                y: y,
+            });
+        }
+    }
+    None
+}
+
+pub fn field_to_tbp(field: &Field) -> Vec<Vec<Option<String>>> {
+    (0..BOARD_ROWS as i32).map(|y| {
+        (0..tetris_board_width as i32 - 2).map(|x| {
+            let (board_x, board_y) = to_board(x, y);
+            if board_y >= 1 && field.is_filled(board_x as usize, board_y as usize) {
+                Some("G".to_string())
+            } else {
+                None
+            }
+        }).collect()
+    }).collect()
+}
+
+// Anything above the top of our board is dropped.
+pub fn field_from_tbp(board: &[Vec<Option<String>>]) -> Field {
+    let mut field = Field::empty(tetris_board_width, tetris_board_height);
+    for (y, row) in board.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let (board_x, board_y) = to_board(x as i32, y as i32);
+            if cell.is_some() && board_y >= 1 && board_x < tetris_board_width as i32 - 1 {
+                field.fill(board_x as usize, board_y as usize);
+            }
+        }
+    }
+    field
+}
+
+// Three corner rule, for a T whose last move was a rotation, either way.
+fn spin(field: &Field, placement: &Placement) -> &'static str {
+    let last_move = placement.moves.iter().rev().find(|&&key_move| key_move != Move::HardDrop);
+    let is_rotation = matches!(last_move, Some(&Move::RotateRight) | Some(&Move::RotateLeft) | Some(&Move::Rotate180));
+    if placement.piece.kind != PieceKind::T || !is_rotation {
+        return "none";
+    }
+    let center = placement.piece.pos;
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    let filled = corners.iter()
+        .filter(|&&(x, y)| field.is_filled((center.x + x) as usize, (center.y + y) as usize))
+        .count();
+    if filled >= 3 { "full" } else { "none" }
+}
+
+pub fn placement_move(field: &Field, placement: &Placement) -> Option<TbpMove> {
+    piece_to_location(&placement.piece).map(|location| TbpMove {
+        location: location,
+        spin: spin(field, placement).to_string(),
+    })
+}
+
+// What a bot knows about the game, kept up to date from the frontend's
+// messages.
+pub struct BotState {
+    pub field: Field,
+    pub queue: Vec<PieceKind>,
+    pub is_running: bool,
+}
+
+impl BotState {
+    fn new() -> Self {
+        BotState {
+            field: Field::empty(tetris_board_width, tetris_board_height),
+            queue: Vec::new(),
+            is_running: false,
+        }
+    }
+
+    pub fn current_piece(&self) -> Option<TetrisPiece> {
+        self.queue.first().map(|&kind| TetrisPiece::spawn(kind, start_pos))
+    }
+
+    pub fn preview(&self, count: usize) -> Vec<TetrisPiece> {
+        self.queue.iter().skip(1).take(count).map(|&kind| TetrisPiece::spawn(kind, start_pos)).collect()
+    }
+
+    fn play(&mut self, piece_move: &TbpMove) {
+        if let Some(piece) = location_to_piece(&piece_move.location) {
+            self.field.place(&piece);
+        }
+        if !self.queue.is_empty() {
+            self.queue.remove(0);
+        }
+    }
+}
+
+fn send<W: Write, M: ::serde::Serialize>(out: &mut W, message: &M) -> io::Result<()> {
+    writeln!(out, "{}", serde_json::to_string(message).unwrap())?;
+    out.flush()
+}
+
+// Speaks TBP on stdin and stdout until told to quit, asking `suggest` for
+// moves, best first.
+pub fn run_bot<F>(name: &str, mut suggest: F) -> io::Result<()>
+    where F: FnMut(&BotState) -> Vec<TbpMove>
+{
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut state = BotState::new();
+
+    send(&mut out, &BotMessage::Info {
+        name: name.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        author: env!("CARGO_PKG_AUTHORS").to_string(),
+        features: Vec::new(),
+    })?;
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        // Messages we don't know about are ignored.
+        let message: FrontendMessage = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        match message {
+            FrontendMessage::Rules { .. } => send(&mut out, &BotMessage::Ready)?,
+            FrontendMessage::Start { queue, board, .. } => {
+                state.field = field_from_tbp(&board);
+                state.queue = queue.iter().filter_map(|letter| PieceKind::from_letter(letter)).collect();
+                state.is_running = true;
+            }
+            FrontendMessage::Stop => state.is_running = false,
+            FrontendMessage::Suggest => {
+                if state.is_running {
+                    send(&mut out, &BotMessage::Suggestion { moves: suggest(&state) })?;
+                }
+            }
+            FrontendMessage::Play { piece_move } => state.play(&piece_move),
+            FrontendMessage::NewPiece { piece } => {
+                if let Some(kind) = PieceKind::from_letter(&piece) {
+                    state.queue.push(kind);
+                }
+            }
+            FrontendMessage::Quit => break,
+        }
+    }
+    Ok(())
+}
+
+#[derive(PartialEq)]
+enum Handshake {
+    WaitingForInfo,
+    WaitingForReady,
+    Ready,
+}
+
+// Plays a board with an external bot process, steering each piece to the
+// bot's suggestion through the normal keys.
+pub struct ExternalBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<BotMessage>,
+    handshake: Handshake,
+    is_started: bool,
+    pieces_locked: Option<u32>,
+    // The field the bot expects once the piece it was told about locks.
+    expected_field: Option<Field>,
+    target: Option<TetrisPiece>,
+}
+
+impl ExternalBot {
+    pub fn spawn(command: &[String]) -> io::Result<Self> {
+        if command.is_empty() || command[0].is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "No command to start the bot with"));
+        }
+        pieces::require_tetrominoes("TBP bots").map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => return,
+                };
+                if let Ok(message) = serde_json::from_str(&line) {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(ExternalBot {
+            name: command[0].clone(),
+            child: child,
+            stdin: stdin,
+            messages: messages,
+            handshake: Handshake::WaitingForInfo,
+            is_started: false,
+            pieces_locked: None,
+            expected_field: None,
+            target: None,
+        })
+    }
+
+    fn send(&mut self, message: &FrontendMessage) {
+        // A bot that has gone away just stops suggesting moves.
+        let _ = send(&mut self.stdin, message);
+    }
+
+    fn queue(board: &TetrisBoard) -> Vec<TetrisPiece> {
+        let mut queue = vec![board.active_piece];
+        queue.extend(board.tetris_gen.preview(PREVIEW_SIZE, start_pos));
+        queue
+    }
+
+    // Tells the bot about the new piece, starting over with the whole board
+    // if it no longer matches what the bot thinks it is.
+    fn on_new_piece(&mut self, board: &TetrisBoard) {
+        let field = Field::from_board(board);
+        let queue = ExternalBot::queue(board);
+        if self.is_started && self.expected_field.as_ref() == Some(&field) {
+            let piece = queue.last().unwrap().kind.letter().to_string();
+            self.send(&FrontendMessage::NewPiece { piece: piece });
+        } else {
+            if self.is_started {
+                self.send(&FrontendMessage::Stop);
+            }
+            self.send(&FrontendMessage::Start {
+                hold: None,
+                queue: queue.iter().map(|piece| piece.kind.letter().to_string()).collect(),
+                combo: 0,
+                back_to_back: false,
+                board: field_to_tbp(&field),
+            });
+            self.is_started = true;
+        }
+        self.send(&FrontendMessage::Suggest);
+        self.expected_field = None;
+        self.target = None;
+    }
+
+    // Takes the first suggested move we can actually get the piece to.
+    fn on_suggestion(&mut self, moves: Vec<TbpMove>, board: &TetrisBoard) {
+        let field = Field::from_board(board);
+        for piece_move in moves {
+            let piece = match location_to_piece(&piece_move.location) {
+                Some(piece) => piece,
+                None => continue,
+            };
+            if ai::path_to(&field, &board.active_piece, &piece).is_some() {
+                let mut expected_field = field.clone();
+                expected_field.place(&piece);
+                self.expected_field = Some(expected_field);
+                self.target = Some(piece);
+                self.send(&FrontendMessage::Play { piece_move: piece_move });
+                return;
+            }
+        }
+    }
+}
+
+impl Controller for ExternalBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn next_input(&mut self, board: &TetrisBoard) -> Input {
+        let mut input = Input::new();
+
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                BotMessage::Info { name, .. } => {
+                    self.name = name;
+                    if self.handshake == Handshake::WaitingForInfo {
+                        self.send(&FrontendMessage::Rules { randomizer: "seven_bag".to_string() });
+                        self.handshake = Handshake::WaitingForReady;
+                    }
+                }
+                BotMessage::Ready => self.handshake = Handshake::Ready,
+                BotMessage::Suggestion { moves } => self.on_suggestion(moves, board),
+                BotMessage::Error { reason } => eprintln!("{}: {}", self.name, reason),
+            }
+        }
+
+        if self.handshake != Handshake::Ready || board.is_game_over || !board.is_piece_active() {
+            return input;
+        }
+
+        if self.pieces_locked != Some(board.pieces_locked) {
+            self.pieces_locked = Some(board.pieces_locked);
+            self.on_new_piece(board);
+        }
+
+        // Wait for the bot to make up its mind.
+        let target = match self.target {
+            Some(target) => target,
+            None => return input,
+        };
+
+        let next_move = ai::path_to(&Field::from_board(board), &board.active_piece, &target)
+            .map_or(Move::HardDrop, |moves| moves[0]);
+        match next_move {
+            Move::Left => input.left_key_pressed = true,
+            Move::Right => input.right_key_pressed = true,
+            Move::RotateRight => input.up_key_pressed = true,
+            Move::RotateLeft => input.rotate_left_key_pressed = true,
+            Move::Rotate180 => input.rotate_180_key_pressed = true,
+            Move::SoftDrop => input.down_key_pressed = true,
+            Move::HardDrop => input.hard_drop_key_pressed = true,
+        }
+        input
+    }
+}
+
+impl Drop for ExternalBot {
+    // Asks the bot to quit, killing it if it doesn't, so a stuck bot can't
+    // hold up the game.
+    fn drop(&mut self) {
+        self.send(&FrontendMessage::Quit);
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(QUIT_TIMEOUT_MS) {
+            match self.child.try_wait() {
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                _ => return,
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use toml;
+
+use frame::Block;
+use super::{Color, PieceKind};
+
+// A theme file, in TOML. See res/themes/flat.toml.
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    background_color: [u8; 3],
+    background: Option<String>,
+    #[serde(default)]
+    colors: HashMap<String, [u8; 3]>,
+    #[serde(default)]
+    tiles: HashMap<String, String>,
+    #[serde(default)]
+    glyphs: Glyphs,
+}
+
+// Marks on each piece's blocks, so pieces can be told apart without their
+// colors.
+#[derive(Copy, Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Glyphs {
+    #[default]
+    None,
+    Letters,
+    // Pieces without a pattern get their letter.
+    Patterns,
+}
+
+impl Glyphs {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Glyphs::None),
+            "letters" => Some(Glyphs::Letters),
+            "patterns" => Some(Glyphs::Patterns),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Glyphs::None => "none",
+            Glyphs::Letters => "letters",
+            Glyphs::Patterns => "patterns",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Pattern {
+    HorizontalLine,
+    VerticalLine,
+    Square,
+    Dot,
+    // Diagonals, going up and going down from left to right.
+    Rising,
+    Falling,
+    Cross,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Glyph {
+    Letter(char),
+    Pattern(Pattern),
+}
+
+// The seven tetrominoes' patterns.
+fn pattern(kind: PieceKind) -> Option<Pattern> {
+    match kind {
+        PieceKind::I => Some(Pattern::HorizontalLine),
+        PieceKind::O => Some(Pattern::Square),
+        PieceKind::T => Some(Pattern::Dot),
+        PieceKind::S => Some(Pattern::Rising),
+        PieceKind::Z => Some(Pattern::Falling),
+        PieceKind::J => Some(Pattern::VerticalLine),
+        PieceKind::L => Some(Pattern::Cross),
+        _ => None,
+    }
+}
+
+// Black on light blocks and white on dark ones.
+pub fn glyph_color(color: Color) -> Color {
+    let luminance = 299 * color.r as u32 + 587 * color.g as u32 + 114 * color.b as u32;
+    if luminance > 128 * 1000 {
+        Color::RGB(0, 0, 0)
+    } else {
+        Color::RGB(255, 255, 255)
+    }
+}
+
+// The names of the themes to pick from: "plain", then the ones in
+// res/themes.
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir("res/themes").into_iter().flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names.insert(0, "plain".to_string());
+    names
+}
+
+// How the blocks and the background look. Blocks are looked up by their
+// tile's name, with "block" standing in for any block without a tile of its
+// own. Images are paths to files, for front ends that can draw them.
+pub struct Theme {
+    pub background_color: Color,
+    pub background: Option<String>,
+    colors: HashMap<String, Color>,
+    pub tiles: HashMap<String, String>,
+    pub glyphs: Glyphs,
+}
+
+impl Theme {
+    // Image paths are from `dir`.
+    pub fn from_toml(text: &str, dir: &Path) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let image_path = |image: &String| dir.join(image).to_string_lossy().into_owned();
+        Ok(Theme {
+            background_color: Color::RGB(file.background_color[0], file.background_color[1], file.background_color[2]),
+            background: file.background.as_ref().map(&image_path),
+            colors: file.colors.iter().map(|(name, color)| (name.clone(), Color::RGB(color[0], color[1], color[2]))).collect(),
+            tiles: file.tiles.iter().map(|(name, image)| (name.clone(), image_path(image))).collect(),
+            glyphs: file.glyphs,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        Theme::from_toml(&text, dir).map_err(|e| format!("Failed to read {}: {}", path, e))
+    }
+
+    // One of the themes in res/themes, "plain", or a theme file.
+    pub fn named(name: &str) -> Result<Self, String> {
+        if name == "plain" {
+            Ok(Theme::plain())
+        } else if name.ends_with(".toml") {
+            Theme::load(name)
+        } else {
+            Theme::load(&format!("res/themes/{}.toml", name))
+        }
+    }
+
+    // Flat blocks in the pieces' own colors, on black.
+    pub fn plain() -> Self {
+        Theme {
+            background_color: Color::RGB(0, 0, 0),
+            background: None,
+            colors: HashMap::new(),
+            tiles: HashMap::new(),
+            glyphs: Glyphs::None,
+        }
+    }
+
+    pub fn color(&self, block: &Block) -> Color {
+        let color = self.colors.get(&block.tile.name()).cloned().unwrap_or(block.color);
+        let glow = |c: u8| (c as u32 + (255 - c as u32) * block.glow as u32 / 255) as u8;
+        Color::RGB(glow(color.r), glow(color.g), glow(color.b))
+    }
+
+    // The mark on a piece's blocks, if they have one.
+    pub fn glyph(&self, kind: PieceKind) -> Option<Glyph> {
+        match self.glyphs {
+            Glyphs::None => None,
+            Glyphs::Letters => Some(Glyph::Letter(kind.letter())),
+            Glyphs::Patterns => Some(pattern(kind).map_or(Glyph::Letter(kind.letter()), Glyph::Pattern)),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use rand::{thread_rng, Rng};
+
+use std::cmp;
+
+use super::{Input, TetrisBoard};
+
+// Lines sent for clearing 0 to 4 lines, or 5 with pentominoes, normally and
+// with a T-spin.
+static ATTACK_TABLE: [u32; 6] = [0, 0, 1, 2, 4, 6];
+static T_SPIN_ATTACK_TABLE: [u32; 4] = [0, 2, 4, 6];
+
+static PERFECT_CLEAR_ATTACK: u32 = 10;
+
+pub struct Player {
+    pub board: TetrisBoard,
+    // Garbage waiting to be pushed onto the board, one entry per attack.
+    pub incoming: Vec<u32>,
+    // Consecutive piece locks that cleared lines, minus one.
+    pub combo: Option<u32>,
+    pub is_back_to_back: bool,
+    pub lines_sent: u32,
+}
+
+impl Player {
+    fn new() -> Self {
+        Player {
+            board: TetrisBoard::new(),
+            incoming: Vec::new(),
+            combo: None,
+            is_back_to_back: false,
+            lines_sent: 0,
+        }
+    }
+
+    pub fn incoming_total(&self) -> u32 {
+        self.incoming.iter().sum()
+    }
+
+    // Works out the attack for the piece that just locked, updating the
+    // combo and back-to-back state.
+    fn attack(&mut self) -> u32 {
+        let lines = self.board.last_clear_lines as usize;
+        let is_t_spin = self.board.last_lock_was_t_spin;
+
+        if lines == 0 {
+            self.combo = None;
+            return 0;
+        }
+
+        let combo = self.combo.map_or(0, |combo| combo + 1);
+        self.combo = Some(combo);
+
+        let is_difficult = lines >= 4 || is_t_spin;
+        let table: &[u32] = if is_t_spin { &T_SPIN_ATTACK_TABLE } else { &ATTACK_TABLE };
+        let mut attack = table[cmp::min(lines, table.len() - 1)];
+        if is_difficult && self.is_back_to_back {
+            attack += 1;
+        }
+        self.is_back_to_back = is_difficult;
+
+        // Each combo step is worth another quarter of the attack, and even
+        // singles and doubles add up once the combo is long enough.
+        attack = attack * (4 + combo) / 4;
+        if attack == 0 && combo >= 2 {
+            attack = (1.0 + 1.25 * combo as f64).ln() as u32;
+        }
+
+        if self.board.is_perfect_clear() {
+            attack += PERFECT_CLEAR_ATTACK;
+        }
+        attack
+    }
+
+    // Cancels incoming garbage with an attack, returning what is left over
+    // to send.
+    fn counter(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 && !self.incoming.is_empty() {
+            if self.incoming[0] > attack {
+                self.incoming[0] -= attack;
+                attack = 0;
+            } else {
+                attack -= self.incoming.remove(0);
+            }
+        }
+        attack
+    }
+
+    // Pushes all incoming garbage onto the board, each attack with its own hole.
+    fn receive_garbage(&mut self) {
+        let width = self.board.field_width();
+        for lines in self.incoming.drain(..) {
+            let hole = thread_rng().gen_range(0, width);
+            for _ in 0..lines {
+                if !self.board.insert_garbage_row(hole) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Two boards side by side. Line clears send garbage to the opponent, and the
+// first player to top out loses.
+pub struct VersusGame {
+    pub players: [Player; 2],
+    pub winner: Option<usize>,
+    pub is_finished: bool,
+    restart_delay: u32,
+    restart_countdown: u32,
+}
+
+impl Default for VersusGame {
+    fn default() -> Self {
+        VersusGame::new()
+    }
+}
+
+impl VersusGame {
+    pub fn new() -> Self {
+        VersusGame {
+            players: [Player::new(), Player::new()],
+            winner: None,
+            is_finished: false,
+            restart_delay: 60,
+            restart_countdown: 0,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.players = [Player::new(), Player::new()];
+        self.winner = None;
+        self.is_finished = false;
+    }
+
+    pub fn update(&mut self, inputs: &[Input]) {
+        if self.is_finished {
+            if self.restart_countdown > 0 {
+                self.restart_countdown -= 1;
+            } else if inputs.iter().any(|input| input.up_key_pressed) {
+                self.restart();
+            }
+            return;
+        }
+
+        let mut outgoing = [0u32; 2];
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let pieces_locked = player.board.pieces_locked;
+            player.board.update(&inputs[i]);
+
+            if player.board.pieces_locked != pieces_locked && !player.board.is_game_over {
+                let attack = player.attack();
+                if attack > 0 {
+                    outgoing[i] = player.counter(attack);
+                    player.lines_sent += outgoing[i];
+                } else if player.board.last_clear_lines == 0 {
+                    player.receive_garbage();
+                }
+            }
+        }
+
+        for (i, &lines) in outgoing.iter().enumerate() {
+            if lines > 0 {
+                self.players[1 - i].incoming.push(lines);
+            }
+        }
+
+        let topped_out: Vec<bool> = self.players.iter().map(|player| player.board.is_game_over).collect();
+        if topped_out[0] || topped_out[1] {
+            self.is_finished = true;
+            self.restart_countdown = self.restart_delay;
+            self.winner = match (topped_out[0], topped_out[1]) {
+                (true, false) => Some(1),
+                (false, true) => Some(0),
+                _ => None,
+            };
+        }
+    }
+}
@@ -0,0 +1,43 @@
+extern crate rust_tetris;
+
+use rust_tetris::{PieceKind, Pos2D, TetrisPiece};
+use rust_tetris::ai::{self, Field, Move};
+
+// Rows from the top, down to the floor.
+fn field(rows: &[&str]) -> Field {
+    let mut field = Field::empty(12, 24);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, cell) in row.chars().enumerate() {
+            if cell == '#' {
+                field.fill(x + 1, 23 - rows.len() + y);
+            }
+        }
+    }
+    field
+}
+
+// A Z tucked under the block on the right can only turn in there to the
+// left, with a kick: turning right three times kicks it somewhere else.
+#[test]
+fn counter_clockwise_kicks() {
+    let field = field(&["#..#.##.#.",
+                        "....#.#..."]);
+    let piece = TetrisPiece::build(PieceKind::Z, Pos2D::xy(5, 2));
+    let target = vec![(2, 21), (3, 21), (3, 22), (4, 22)];
+    let (_, moves) = ai::find_placements(&field, &piece).into_iter()
+        .find(|(landing, _)| ai::cells_key(landing) == target)
+        .expect("The Z should reach the slot");
+    let last_turn = moves.iter().rev().find(|&&key_move| key_move != Move::HardDrop && key_move != Move::SoftDrop);
+    assert_eq!(last_turn, Some(&Move::RotateLeft));
+}
+
+// One press rather than two.
+#[test]
+fn half_turns() {
+    let field = Field::empty(12, 24);
+    let piece = TetrisPiece::build(PieceKind::T, Pos2D::xy(5, 2));
+    let upside_down = piece.rotated_right(|rotated| field.fits(rotated))
+        .and_then(|half| half.rotated_right(|rotated| field.fits(rotated)))
+        .unwrap();
+    assert_eq!(ai::path_to(&field, &piece, &field.drop(&upside_down)), Some(vec![Move::Rotate180, Move::HardDrop]));
+}
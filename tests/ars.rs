@@ -0,0 +1,40 @@
+extern crate rust_tetris;
+
+use rust_tetris::{PieceKind, Pos2D, TetrisPiece};
+use rust_tetris::pieces::{self, PieceSet};
+
+// Fits unless a cell of the piece is on one of the blocked cells.
+fn fits_except(blocked: &[(i32, i32)]) -> Box<dyn Fn(&TetrisPiece) -> bool> {
+    let blocked = blocked.to_vec();
+    Box::new(move |piece: &TetrisPiece| piece.iter().all(|cell| !blocked.contains(&(cell.x, cell.y))))
+}
+
+fn turned_to(kind: PieceKind, blocked: &[(i32, i32)]) -> Option<(i32, i32)> {
+    TetrisPiece::build(kind, Pos2D::xy(5, 5)).rotated_right(fits_except(blocked)).map(|piece| (piece.pos.x, piece.pos.y))
+}
+
+// The piece set is for the whole process, which is why this is a file of its
+// own.
+#[test]
+fn ars_kicks() {
+    pieces::use_piece_set(PieceSet::load("res/pieces/ars.toml").unwrap()).unwrap();
+    // Turning clear of the stack doesn't kick.
+    assert_eq!(turned_to(PieceKind::T, &[]), Some((5, 5)));
+
+    // One column right, then one left.
+    assert_eq!(turned_to(PieceKind::S, &[(4, 4)]), Some((6, 5)));
+    assert_eq!(turned_to(PieceKind::S, &[(4, 4), (6, 6)]), Some((4, 5)));
+    assert_eq!(turned_to(PieceKind::S, &[(4, 4), (6, 6), (3, 4)]), None);
+
+    // The I never kicks.
+    assert_eq!(turned_to(PieceKind::I, &[(6, 4)]), None);
+
+    // The L, J and T don't when the first blocked cell is in their center
+    // column, but do when it's to the side.
+    assert_eq!(turned_to(PieceKind::T, &[(5, 4)]), None);
+    assert_eq!(turned_to(PieceKind::T, &[(4, 5)]), Some((6, 5)));
+    assert_eq!(turned_to(PieceKind::T, &[(5, 4), (4, 5)]), None);
+    assert_eq!(turned_to(PieceKind::L, &[(5, 4)]), None);
+    assert_eq!(turned_to(PieceKind::L, &[(4, 4)]), Some((6, 5)));
+    assert_eq!(turned_to(PieceKind::J, &[(5, 4)]), None);
+}
@@ -0,0 +1,139 @@
+extern crate rust_tetris;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use rust_tetris::{Input, TetrisBoard};
+use rust_tetris::controls::{self, Action, Controls, ControlsMenu};
+use rust_tetris::finesse::FinesseTracker;
+use rust_tetris::frame::Frame;
+use rust_tetris::game::Game;
+use rust_tetris::theme::Glyphs;
+
+// A held key repeating in the same frame as a fresh press of another, in
+// either order: only the held one is a repeat.
+#[test]
+fn repeats_are_per_key() {
+    let mut input = Input::new();
+    controls::press(&mut input, &[Action::MoveLeft], true);
+    controls::press(&mut input, &[Action::RotateRight], false);
+    assert!(input.left_key_pressed && input.left_is_repeat);
+    assert!(input.up_key_pressed && !input.up_is_repeat);
+
+    let mut input = Input::new();
+    controls::press(&mut input, &[Action::RotateRight], false);
+    controls::press(&mut input, &[Action::MoveLeft], true);
+    assert!(input.left_key_pressed && input.left_is_repeat);
+    assert!(input.up_key_pressed && !input.up_is_repeat);
+}
+
+// A fresh press beats the same key repeating.
+#[test]
+fn fresh_press_beats_repeat() {
+    let mut input = Input::new();
+    controls::press(&mut input, &[Action::MoveRight], false);
+    controls::press(&mut input, &[Action::MoveRight], true);
+    assert!(input.right_key_pressed && !input.right_is_repeat);
+}
+
+#[test]
+fn bits_round_trip() {
+    let mut input = Input::new();
+    controls::press(&mut input, &[Action::MoveLeft, Action::Rotate180], true);
+    controls::press(&mut input, &[Action::RotateLeft, Action::Hold], false);
+    let bits = input.to_bits();
+    assert_eq!(Input::from_bits(bits).to_bits(), bits);
+    let read = Input::from_bits(bits);
+    assert!(read.left_is_repeat && read.rotate_180_is_repeat && !read.rotate_left_is_repeat && read.hold_key_pressed);
+}
+
+// The game's own keys would never reach the action.
+#[test]
+fn menu_refuses_reserved_keys() {
+    let mut controls = Controls::default();
+    let mut menu = ControlsMenu::new();
+    menu.is_waiting = true;
+    assert!(menu.add_key(&mut controls, "E").is_err());
+    assert!(menu.add_key(&mut controls, "f11").is_err());
+    assert!(menu.is_waiting);
+    assert!(menu.add_key(&mut controls, "J").is_ok());
+    assert!(!menu.is_waiting);
+    assert!(controls.single.move_left.contains(&"J".to_string()));
+}
+
+#[test]
+fn load_refuses_reserved_keys() {
+    let path = env::temp_dir().join("rust-tetris-reserved-controls.toml");
+    let path = path.to_str().unwrap();
+    File::create(path).unwrap().write_all(b"[single]\nhold = [\"C\", \"h\"]\n").unwrap();
+    let error = Controls::load(path).err().unwrap();
+    assert!(error.contains("h can't be bound"), "{}", error);
+
+    File::create(path).unwrap().write_all(b"[single]\nhold = [\"C\"]\n").unwrap();
+    assert_eq!(Controls::load(path).unwrap().single.hold, vec!["C".to_string()]);
+}
+
+// Games start over with rotate right, whichever key that is.
+#[test]
+fn banners_name_the_restart_key() {
+    let mut board = TetrisBoard::with_seed(0);
+    board.is_game_over = true;
+    let game = Game::Marathon(board);
+    let mut controls = Controls::default();
+    controls.single.rotate_right = vec!["K".to_string()];
+    let frame = Frame::new(&game, &FinesseTracker::new(), &controls, &[]);
+    assert!(frame.panels.iter().flat_map(|panel| panel.lines.iter()).any(|line| line.text == "Press K to restart"));
+}
+
+// The settings page goes through the themes, and the pick is saved with the
+// keys.
+#[test]
+fn menu_picks_a_theme_to_save() {
+    let mut controls = Controls::default();
+    let mut menu = ControlsMenu::new();
+    menu.themes = vec!["plain".to_string(), "bevel".to_string()];
+    menu.move_by(0, -1);
+    assert!(menu.is_settings_page());
+    assert_eq!(menu.row, 0);
+    menu.select(&mut controls);
+    assert!(!menu.is_waiting);
+    assert_eq!(controls.theme, "bevel");
+    menu.select(&mut controls);
+    assert_eq!(controls.theme, "plain");
+    menu.select(&mut controls);
+    // Clearing keys leaves the settings alone.
+    menu.clear_keys(&mut controls);
+    assert_eq!(controls.single.move_left, Controls::default().single.move_left);
+
+    let path = env::temp_dir().join("rust-tetris-theme-controls.toml");
+    let path = path.to_str().unwrap();
+    controls.save(path).unwrap();
+    assert_eq!(Controls::load(path).unwrap().theme, "bevel");
+    File::create(path).unwrap().write_all(b"[single]\nhold = [\"C\"]\n").unwrap();
+    assert_eq!(Controls::load(path).unwrap().theme, "plain");
+}
+
+// Glyphs are the theme's own until one is picked.
+#[test]
+fn menu_picks_glyphs_to_save() {
+    let mut controls = Controls::default();
+    let mut menu = ControlsMenu::new();
+    menu.move_by(-1, -1);
+    assert!(menu.is_settings_page());
+    assert_eq!(menu.row, 1);
+    menu.select(&mut controls);
+    assert_eq!(controls.glyphs, Some(Glyphs::None));
+    menu.select(&mut controls);
+    menu.select(&mut controls);
+    assert_eq!(controls.glyphs, Some(Glyphs::Patterns));
+
+    let path = env::temp_dir().join("rust-tetris-glyph-controls.toml");
+    let path = path.to_str().unwrap();
+    controls.save(path).unwrap();
+    assert_eq!(Controls::load(path).unwrap().glyphs, Some(Glyphs::Patterns));
+    menu.select(&mut controls);
+    assert_eq!(controls.glyphs, None);
+    controls.save(path).unwrap();
+    assert_eq!(Controls::load(path).unwrap().glyphs, None);
+}
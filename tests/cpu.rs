@@ -0,0 +1,30 @@
+extern crate rust_tetris;
+
+use rust_tetris::{Controller, TetrisBoard};
+use rust_tetris::cpu::{Cpu, Difficulty};
+
+#[test]
+fn harder_cpus_play_better() {
+    let (easy, medium, hard) = (Difficulty::named("easy"), Difficulty::named("medium"), Difficulty::named("hard"));
+    assert!(easy.pieces_per_second < medium.pieces_per_second && medium.pieces_per_second < hard.pieces_per_second);
+    assert!(easy.lookahead < medium.lookahead && medium.lookahead < hard.lookahead);
+    assert!(easy.mistake_rate > medium.mistake_rate && medium.mistake_rate > hard.mistake_rate);
+    assert_eq!(Difficulty::named("anything"), medium);
+}
+
+// Each difficulty gets its pieces down.
+#[test]
+fn cpu_locks_pieces() {
+    for &difficulty in ["easy", "medium", "hard"].iter() {
+        let mut cpu = Cpu::with_seed(difficulty, 1);
+        let mut board = TetrisBoard::with_seed(1);
+        let mut frames = 0;
+        while board.pieces_locked < 2 && frames < 60 * 10 {
+            let input = cpu.next_input(&board);
+            board.update(&input);
+            frames += 1;
+        }
+        assert_eq!(board.pieces_locked, 2, "{} didn't lock its pieces", cpu.name());
+        assert!(!board.is_game_over);
+    }
+}
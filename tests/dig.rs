@@ -0,0 +1,34 @@
+extern crate rust_tetris;
+
+use rust_tetris::TetrisBoard;
+use rust_tetris::dig::DigGame;
+
+// The column of each garbage row's hole, from the bottom up.
+fn holes(board: &TetrisBoard) -> Vec<usize> {
+    board.board[1..board.height - 1].iter().rev()
+        .filter(|row| row.iter().any(|block| block.is_garbage))
+        .map(|row| row[1..board.width - 1].iter().position(|block| !block.is_filled).unwrap())
+        .collect()
+}
+
+#[test]
+fn messiness_moves_the_holes() {
+    let tidy = DigGame::with_seed(10, 10, 0.0, 1);
+    let holes_tidy = holes(&tidy.board);
+    assert_eq!(holes_tidy.len(), 10);
+    assert!(holes_tidy.iter().all(|&hole| hole == holes_tidy[0]));
+
+    let messy = DigGame::with_seed(10, 10, 1.0, 1);
+    let holes_messy = holes(&messy.board);
+    assert_eq!(holes_messy.len(), 10);
+    assert!(holes_messy.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+// No more garbage than the goal, however many rows the board starts with.
+#[test]
+fn starts_with_at_most_the_goal() {
+    let dig_game = DigGame::with_seed(3, 10, 0.5, 1);
+    assert_eq!(dig_game.board.garbage_rows(), 3);
+    assert_eq!(dig_game.garbage_cleared(), 0);
+    assert!(!dig_game.is_complete);
+}
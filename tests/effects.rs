@@ -0,0 +1,57 @@
+extern crate rust_tetris;
+
+use rust_tetris::{Color, Input, RandomTetrisPieceGenerator, TetrisBoard, TetrisUnitBlock};
+use rust_tetris::controls::Controls;
+use rust_tetris::effects::{EffectSettings, Effects};
+use rust_tetris::finesse::FinesseTracker;
+use rust_tetris::frame::Frame;
+use rust_tetris::game::Game;
+use rust_tetris::tbp::{self, PieceLocation};
+
+// Where the frame draws blocks, row by row.
+fn filled(frame: &Frame) -> Vec<Vec<bool>> {
+    frame.boards[0].blocks.iter().map(|row| row.iter().map(|block| block.is_some()).collect()).collect()
+}
+
+fn hard_drop(game: &mut Game, effects: &mut Effects) {
+    let mut input = Input::new();
+    input.hard_drop_key_pressed = true;
+    game.update(&[input]);
+    effects.update(game);
+}
+
+// Without a line clear delay the rows go at once, so the effect mustn't draw
+// the stack from before over pieces that lock after.
+#[test]
+fn clears_without_delay_show_the_board() {
+    let garbage = TetrisUnitBlock { is_filled: true, color: Color::RGB(128, 128, 128), is_garbage: true, kind: None };
+    let empty = TetrisUnitBlock { is_filled: false, color: Color::RGB(0, 0, 0), is_garbage: false, kind: None };
+    // A bottom row the I fills.
+    let mut row = vec![garbage; 10];
+    for cell in row[3..7].iter_mut() {
+        *cell = empty;
+    }
+    let mut board = TetrisBoard::with_setup(&[row], RandomTetrisPieceGenerator::with_seed(1));
+    board.active_piece = tbp::location_to_piece(&PieceLocation {
+        kind: "I".to_string(),
+        orientation: "north".to_string(),
+        x: 4,
+        y: 10,
+    }).unwrap();
+    let mut game = Game::Marathon(board);
+    let mut effects = Effects::new(EffectSettings::from_names("clears").unwrap());
+    let finesse = FinesseTracker::new();
+    // Effects start from the board as they first see it.
+    effects.update(&game);
+
+    hard_drop(&mut game, &mut effects);
+    assert_eq!(game.board().unwrap().last_clear_lines, 1);
+    for _ in 0..2 {
+        let plain = Frame::new(&game, &finesse, &Controls::default(), &[]);
+        let mut shown = Frame::new(&game, &finesse, &Controls::default(), &[]);
+        effects.apply(&mut shown);
+        assert!(filled(&shown) == filled(&plain));
+        // And the next piece, straight after.
+        hard_drop(&mut game, &mut effects);
+    }
+}
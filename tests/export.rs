@@ -0,0 +1,63 @@
+extern crate gif;
+extern crate png;
+extern crate rust_tetris;
+
+use std::env;
+use std::fs::File;
+
+use rust_tetris::export::AnimationWriter;
+
+// Frames 8 by 6, red on the left and blue on the right, with each row
+// padded out to 32 bytes as surfaces can be.
+fn write_frames(path: &str, frames: u32) {
+    let mut pixels = vec![0u8; 32 * 6];
+    for y in 0..6 {
+        for x in 0..8 {
+            let i = y * 32 + x * 3;
+            let channel = if x < 4 { 0 } else { 2 };
+            pixels[i + channel] = 255;
+        }
+    }
+    let mut animation = AnimationWriter::create(path, 8, 6, 0.5, 2, frames).unwrap();
+    for _ in 0..frames {
+        animation.write_frame(&pixels, 32).unwrap();
+    }
+    animation.finish().unwrap();
+}
+
+#[test]
+fn writes_gifs() {
+    let path = env::temp_dir().join("rust-tetris-export.gif");
+    let path = path.to_str().unwrap();
+    write_frames(path, 3);
+
+    let mut reader = gif::Decoder::new(File::open(path).unwrap()).read_info().unwrap();
+    assert_eq!((reader.width(), reader.height()), (4, 3));
+    let mut frames = 0;
+    while let Some(frame) = reader.next_frame_info().unwrap() {
+        // 2 frames of the game is 3 hundredths of a second.
+        assert_eq!(frame.delay, 3);
+        frames += 1;
+    }
+    assert_eq!(frames, 3);
+}
+
+#[test]
+fn writes_apngs() {
+    let path = env::temp_dir().join("rust-tetris-export.png");
+    let path = path.to_str().unwrap();
+    write_frames(path, 4);
+
+    let mut reader = png::Decoder::new(File::open(path).unwrap()).read_info().unwrap();
+    assert_eq!((reader.info().width, reader.info().height), (4, 3));
+    assert_eq!(reader.info().animation_control.unwrap().num_frames, 4);
+    let mut frame = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut frame).unwrap();
+    assert_eq!(&frame[..3], &[255, 0, 0]);
+    assert_eq!(&frame[9..12], &[0, 0, 255]);
+}
+
+#[test]
+fn refuses_unknown_formats() {
+    assert!(AnimationWriter::create("replay.mp4", 8, 6, 1.0, 1, 1).is_err());
+}
@@ -0,0 +1,34 @@
+extern crate rust_tetris;
+
+use rust_tetris::{PieceKind, TetrisBoard, TetrisPiece};
+use rust_tetris::ai::Field;
+use rust_tetris::finesse;
+
+// The spawned piece turned, then dropped where it is.
+fn presses(kind: PieceKind, turn: fn(&TetrisPiece, &Field) -> TetrisPiece) -> Option<u32> {
+    let board = TetrisBoard::new();
+    let field = Field::from_board(&board);
+    let spawn = TetrisPiece::build(kind, board.spawn_pos);
+    let locked = field.drop(&turn(&spawn, &field));
+    finesse::minimal_inputs(&field, &spawn, &locked)
+}
+
+fn right(piece: &TetrisPiece, field: &Field) -> TetrisPiece {
+    piece.rotated_right(|rotated| field.fits(rotated)).unwrap()
+}
+
+fn left(piece: &TetrisPiece, field: &Field) -> TetrisPiece {
+    piece.rotated_left(|rotated| field.fits(rotated)).unwrap()
+}
+
+fn half(piece: &TetrisPiece, field: &Field) -> TetrisPiece {
+    right(&right(piece, field), field)
+}
+
+#[test]
+fn rotations_either_way() {
+    assert_eq!(presses(PieceKind::J, right), Some(1));
+    // Rather than three turns right, or two.
+    assert_eq!(presses(PieceKind::J, left), Some(1));
+    assert_eq!(presses(PieceKind::J, half), Some(1));
+}
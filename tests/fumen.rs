@@ -0,0 +1,127 @@
+extern crate rust_tetris;
+
+use rust_tetris::{Color, PieceKind, RandomTetrisPieceGenerator, TetrisBoard, TetrisUnitBlock};
+use rust_tetris::fumen::{self, Page};
+use rust_tetris::tbp::PieceLocation;
+
+// The empty page every fumen editor starts with.
+static EMPTY_FUMEN: &str = "v115@vhAAgH";
+
+fn block(kind: Option<PieceKind>) -> TetrisUnitBlock {
+    TetrisUnitBlock {
+        is_filled: true,
+        // The same color for every piece, which shouldn't matter.
+        color: Color::RGB(255, 255, 255),
+        is_garbage: kind.is_none(),
+        kind: kind,
+    }
+}
+
+fn empty() -> TetrisUnitBlock {
+    TetrisUnitBlock {
+        is_filled: false,
+        color: Color::RGB(0, 0, 0),
+        is_garbage: false,
+        kind: None,
+    }
+}
+
+// An L, a J and an I on garbage with a hole on the left.
+fn board() -> TetrisBoard {
+    let mut rows = vec![vec![empty(); 10]; 3];
+    for cell in rows[0][0..3].iter_mut() {
+        *cell = block(Some(PieceKind::L));
+    }
+    rows[1][0] = block(Some(PieceKind::L));
+    for cell in rows[1][3..6].iter_mut() {
+        *cell = block(Some(PieceKind::J));
+    }
+    rows[0][5] = block(Some(PieceKind::J));
+    for cell in rows[1][6..10].iter_mut() {
+        *cell = block(Some(PieceKind::I));
+    }
+    for cell in rows[2][1..10].iter_mut() {
+        *cell = block(None);
+    }
+    TetrisBoard::with_setup(&rows, RandomTetrisPieceGenerator::with_seed(0))
+}
+
+#[test]
+fn empty_page() {
+    let pages = fumen::decode(EMPTY_FUMEN).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert!(pages[0].piece.is_none());
+    assert!(pages[0].field.iter().all(|row| row.iter().all(|&value| value == 0)));
+    assert_eq!(fumen::encode(&pages), EMPTY_FUMEN);
+}
+
+#[test]
+fn decodes_links() {
+    let pages = fumen::decode(&format!("https://harddrop.com/fumen/?{}", EMPTY_FUMEN)).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert!(fumen::decode("v114@vhAAgH").is_err());
+}
+
+// Blocks are told apart by their piece, not their color.
+#[test]
+fn pieces_from_kinds() {
+    let page = Page::from_board(&board(), None).unwrap();
+    let bottom = page.field.len() - 2;
+    // I, L, O, Z, T, J, S are 1 to 7, and garbage is 8.
+    assert_eq!(page.field[bottom - 2], vec![2, 2, 2, 0, 0, 6, 0, 0, 0, 0]);
+    assert_eq!(page.field[bottom - 1], vec![2, 0, 0, 6, 6, 6, 1, 1, 1, 1]);
+    assert_eq!(page.field[bottom], vec![0, 8, 8, 8, 8, 8, 8, 8, 8, 8]);
+}
+
+#[test]
+fn board_round_trip() {
+    let mut page = Page::from_board(&board(), Some("Dig: the left, then the I".to_string())).unwrap();
+    page.piece = Some(PieceLocation {
+        kind: "T".to_string(),
+        orientation: "east".to_string(),
+        x: 4,
+        y: 10,
+    });
+    let data = fumen::encode(&[page]);
+    assert!(data.starts_with("v115@"));
+
+    let pages = fumen::decode(&data).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].comment.as_deref(), Some("Dig: the left, then the I"));
+    let piece = pages[0].piece.as_ref().unwrap();
+    assert_eq!((piece.kind.as_str(), piece.orientation.as_str(), piece.x, piece.y), ("T", "east", 4, 10));
+    assert_eq!(pages[0].field, Page::from_board(&board(), None).unwrap().field);
+    assert_eq!(fumen::encode(&pages), data);
+
+    // And back to a board with the same stack.
+    let decoded = pages[0].to_board();
+    let original = board();
+    for (row, original_row) in decoded.board.iter().zip(original.board.iter()) {
+        for (block, original_block) in row.iter().zip(original_row.iter()) {
+            assert_eq!((block.is_filled, block.is_garbage, block.kind), (original_block.is_filled, original_block.is_garbage, original_block.kind));
+        }
+    }
+    assert_eq!(decoded.active_piece.kind, PieceKind::T);
+}
+
+// Later pages store only what changed, and lock the piece before.
+#[test]
+fn pages_round_trip() {
+    let first = Page::from_board(&board(), None).unwrap();
+    let mut pages = vec![first];
+    pages[0].piece = Some(PieceLocation { kind: "O".to_string(), orientation: "north".to_string(), x: 0, y: 3 });
+    let second_field = fumen::decode(&fumen::encode(&pages)).unwrap()[0].field.clone();
+    pages.push(Page {
+        field: second_field,
+        piece: None,
+        comment: None,
+        lock: true,
+        rise: false,
+        mirror: false,
+    });
+    let data = fumen::encode(&pages);
+    let decoded = fumen::decode(&data).unwrap();
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[1].field, pages[1].field);
+    assert_eq!(fumen::encode(&decoded), data);
+}
@@ -0,0 +1,50 @@
+extern crate rust_tetris;
+
+use rust_tetris::Input;
+use rust_tetris::master::MasterGame;
+
+fn hard_drop(master_game: &mut MasterGame) {
+    let mut input = Input::new();
+    input.hard_drop_key_pressed = true;
+    master_game.update(&input);
+}
+
+// Frames from a piece locking to the next one coming in.
+fn entry_delay(master_game: &mut MasterGame) -> u32 {
+    hard_drop(master_game);
+    let mut frames = 0;
+    while !master_game.board.is_piece_active() {
+        master_game.update(&Input::new());
+        frames += 1;
+    }
+    frames
+}
+
+#[test]
+fn grades_by_score() {
+    let mut master_game = MasterGame::with_seed(1);
+    assert_eq!(master_game.grade(), "9");
+    master_game.score = 1400;
+    assert_eq!(master_game.grade(), "6");
+    master_game.score = 16000;
+    assert_eq!(master_game.grade(), "S1");
+    master_game.score = 200000;
+    assert_eq!(master_game.grade(), "S9");
+    // Finishing without missing a checkpoint.
+    master_game.is_complete = true;
+    assert_eq!(master_game.grade(), "GM");
+}
+
+#[test]
+fn delays_shorten_by_section() {
+    let mut master_game = MasterGame::with_seed(1);
+    let first = entry_delay(&mut master_game);
+    // Each piece coming in is a level.
+    assert_eq!(master_game.level, 1);
+    assert_eq!(master_game.section_goal(), 100);
+
+    master_game.level = 700;
+    let later = entry_delay(&mut master_game);
+    assert_eq!(first - later, 25 - 16);
+    assert_eq!(master_game.section_goal(), 800);
+}
@@ -0,0 +1,58 @@
+extern crate rust_tetris;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_tetris::{Input, PieceKind};
+use rust_tetris::ai::Field;
+use rust_tetris::pc::{self, PcGame};
+
+// Rows from the top, down to the floor.
+fn field(rows: &[&str]) -> Field {
+    let mut field = Field::empty(12, 24);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, cell) in row.chars().enumerate() {
+            if cell == '#' {
+                field.fill(x + 1, 23 - rows.len() + y);
+            }
+        }
+    }
+    field
+}
+
+// A four by four well: the I pieces go up either side, or lie across the top
+// and bottom, with the O pieces between.
+#[test]
+fn solves_a_four_line_perfect_clear() {
+    let start = field(&["....######", "....######", "....######", "....######"]);
+    let solutions = pc::solve(&start, PieceKind::I, None, &[PieceKind::O, PieceKind::O, PieceKind::I]);
+    assert!(!solutions.is_empty());
+    for steps in solutions.iter() {
+        assert_eq!(steps.len(), 4);
+        let mut board = start.clone();
+        let lines: u32 = steps.iter().map(|step| board.place(&step.piece)).sum();
+        assert_eq!(lines, 4);
+        assert!(board == Field::empty(12, 24));
+    }
+
+    // No S fits beside three O pieces.
+    assert!(pc::solve(&start, PieceKind::O, None, &[PieceKind::O, PieceKind::O, PieceKind::S]).is_empty());
+}
+
+// Starting over while a search is going calls it off, and only the answer
+// for the board as it is now comes back.
+#[test]
+fn solves_the_latest_board() {
+    let mut game = PcGame::with_seed(0).unwrap();
+    for _ in 0..10 {
+        game.restart();
+    }
+    let started = Instant::now();
+    while game.is_solving() {
+        assert!(started.elapsed() < Duration::from_secs(30));
+        thread::sleep(Duration::from_millis(10));
+        game.update(&Input::new());
+    }
+    let hint = game.hint();
+    assert!(hint.is_none_or(|step| step.piece.kind == game.board.active_piece.kind || step.is_hold));
+}
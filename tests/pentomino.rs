@@ -0,0 +1,25 @@
+extern crate rust_tetris;
+
+use rust_tetris::TetrisBoard;
+use rust_tetris::fumen::{self, Page};
+use rust_tetris::pc::PcGame;
+use rust_tetris::pieces::{self, PieceSet};
+use rust_tetris::puzzle::Puzzle;
+use rust_tetris::tbp::ExternalBot;
+
+// The pentominoes reuse the letters I, L, T and Z, so anything that only
+// knows the seven tetrominoes has to turn them away. The piece set is for
+// the whole process, which is why this is a file of its own.
+#[test]
+fn tetromino_formats_refuse_pentominoes() {
+    assert!(PieceSet::standard().is_tetrominoes());
+    let pentominoes = PieceSet::load("res/pieces/pentomino.toml").unwrap();
+    assert!(!pentominoes.is_tetrominoes());
+    pieces::use_piece_set(pentominoes).unwrap();
+
+    assert!(Page::from_board(&TetrisBoard::with_seed(0), None).is_err());
+    assert!(fumen::decode("v115@vhAAgH").is_err());
+    assert!(Puzzle::load("res/puzzles/tetris.toml").is_err());
+    assert!(PcGame::with_seed(0).is_err());
+    assert!(ExternalBot::spawn(&["tbp-mock-bot".to_string()]).is_err());
+}
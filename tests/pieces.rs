@@ -0,0 +1,64 @@
+extern crate rust_tetris;
+
+use rust_tetris::{PieceKind, Pos2D, TetrisPiece};
+use rust_tetris::pieces::{PieceSet, MAX_PIECE_CELLS};
+
+// A piece set file of one piece with the given cells, in every rotation state.
+fn one_piece(cells: &[(i32, i32)]) -> String {
+    let cells: Vec<String> = cells.iter().map(|&(x, y)| format!("[{}, {}]", x, y)).collect();
+    format!("[[pieces]]\nletter = \"X\"\ncolor = [1, 2, 3]\nrotations = [[{}]]\n", cells.join(", "))
+}
+
+#[test]
+fn loads_the_piece_sets() {
+    for &(path, rotation_system) in [
+        ("res/pieces/standard.toml", "SRS"),
+        ("res/pieces/srs.toml", "SRS"),
+        ("res/pieces/ars.toml", "ARS"),
+    ].iter() {
+        let set = PieceSet::load(path).unwrap();
+        assert!(set.is_tetrominoes(), "{}", path);
+        assert_eq!(set.rotation_system.name(), rotation_system);
+    }
+    let srs = PieceSet::load("res/pieces/srs.toml").unwrap();
+    let t = srs.get(PieceKind::T).unwrap();
+    assert_eq!(t.kicks.right_kicks(0).len(), 5);
+    assert_eq!(t.kicks.left_kicks(4).len(), 5);
+    assert!(PieceSet::load("res/pieces/missing.toml").is_err());
+}
+
+#[test]
+fn pieces_fit_in_max_piece_cells() {
+    let cells: Vec<(i32, i32)> = (0..MAX_PIECE_CELLS as i32 + 1).map(|x| (x, 0)).collect();
+    let set = PieceSet::from_toml(&one_piece(&cells[..MAX_PIECE_CELLS])).unwrap();
+    let piece = set.get(PieceKind('X')).unwrap();
+    assert_eq!(piece.size, MAX_PIECE_CELLS);
+    assert!(!set.is_tetrominoes());
+
+    assert!(PieceSet::from_toml(&one_piece(&cells)).is_err());
+    assert!(PieceSet::from_toml(&one_piece(&[])).is_err());
+}
+
+#[test]
+fn refuses_bad_piece_sets() {
+    let piece = one_piece(&[(0, 0)]);
+    assert!(PieceSet::from_toml("pieces = []").is_err());
+    assert!(PieceSet::from_toml(&format!("{}{}", piece, piece)).is_err());
+    assert!(PieceSet::from_toml(&format!("rotation_system = \"nes\"\n{}", piece)).is_err());
+    assert!(PieceSet::from_toml(&format!("{}kicks = \"missing\"\n", piece)).is_err());
+    // Rotation states with different numbers of cells.
+    assert!(PieceSet::from_toml("[[pieces]]\nletter = \"X\"\ncolor = [1, 2, 3]\nrotations = [[[0, 0]], [[0, 0], [1, 0]]]\n").is_err());
+}
+
+// The standard pieces have no kicks, so a blocked turn doesn't happen.
+#[test]
+fn standard_pieces_turn_only_where_they_are() {
+    let t = TetrisPiece::build(PieceKind::T, Pos2D::xy(5, 5));
+    let turned = t.rotated_right(|_| true).unwrap();
+    assert_eq!(turned.orientation, 1);
+    assert_eq!((turned.pos.x, turned.pos.y), (5, 5));
+    assert_eq!(t.rotated_left(|_| true).unwrap().orientation, 3);
+
+    // Anywhere but where the turn would put it.
+    assert!(t.rotated_right(|piece| piece.orientation != 1 || piece.pos.x != 5 || piece.pos.y != 5).is_none());
+}
@@ -0,0 +1,52 @@
+extern crate rust_tetris;
+
+use rust_tetris::Input;
+use rust_tetris::ai::Field;
+use rust_tetris::pc;
+use rust_tetris::puzzle::{Goal, Puzzle, PuzzleGame};
+
+fn hard_drop(puzzle_game: &mut PuzzleGame) {
+    let mut input = Input::new();
+    input.hard_drop_key_pressed = true;
+    puzzle_game.update(&input);
+    while !puzzle_game.board.is_piece_active() && !puzzle_game.is_solved && !puzzle_game.is_failed {
+        puzzle_game.update(&Input::new());
+    }
+}
+
+// Dropping the pieces where they come in doesn't clear four lines, and up
+// gives it another go.
+#[test]
+fn fails_without_reaching_the_goal() {
+    let mut puzzle_game = PuzzleGame::new(Puzzle::load("res/puzzles/tetris.toml").unwrap());
+    assert!(puzzle_game.puzzle.goal == Goal::ClearLines { lines: 4 });
+    assert_eq!(puzzle_game.pieces_left(), 3);
+    for _ in 0..3 {
+        hard_drop(&mut puzzle_game);
+    }
+    assert!(puzzle_game.is_failed && !puzzle_game.is_solved);
+
+    let mut input = Input::new();
+    input.up_key_pressed = true;
+    puzzle_game.update(&input);
+    assert!(!puzzle_game.is_failed);
+    assert_eq!(puzzle_game.attempts, 2);
+    assert_eq!(puzzle_game.pieces_left(), 3);
+}
+
+// Placing the pieces as the perfect clear solver says.
+#[test]
+fn solves_a_perfect_clear() {
+    let mut puzzle_game = PuzzleGame::new(Puzzle::load("res/puzzles/perfect-clear.toml").unwrap());
+    assert!(puzzle_game.puzzle.goal == Goal::PerfectClear);
+    let field = Field::from_board(&puzzle_game.board);
+    let pieces = puzzle_game.puzzle.pieces.clone();
+    let solutions = pc::solve(&field, pieces[0], None, &pieces[1..]);
+    let steps = solutions.iter().find(|steps| steps.iter().all(|step| !step.is_hold)).unwrap();
+    for step in steps.iter() {
+        assert!(!puzzle_game.is_solved);
+        puzzle_game.board.active_piece = step.piece;
+        hard_drop(&mut puzzle_game);
+    }
+    assert!(puzzle_game.is_solved && !puzzle_game.is_failed);
+}
@@ -0,0 +1,97 @@
+extern crate rust_tetris;
+
+use rust_tetris::{Color, Input, RandomTetrisPieceGenerator, TetrisBoard, TetrisUnitBlock};
+use rust_tetris::game::Game;
+use rust_tetris::master::MasterGame;
+use rust_tetris::sound::{self, Sound, SoundTracker};
+use rust_tetris::tbp::{self, PieceLocation};
+
+fn hard_drop(game: &mut Game, tracker: &mut SoundTracker) -> Vec<Sound> {
+    let mut input = Input::new();
+    input.hard_drop_key_pressed = true;
+    game.update(&[input]);
+    tracker.update(game)
+}
+
+// A tracker that has seen the game's first frame.
+fn tracker(game: &Game) -> SoundTracker {
+    let mut tracker = SoundTracker::new();
+    assert!(tracker.update(game).is_empty());
+    tracker
+}
+
+#[test]
+fn lock() {
+    let mut game = Game::Marathon(TetrisBoard::with_seed(1));
+    let mut tracker = tracker(&game);
+    assert_eq!(hard_drop(&mut game, &mut tracker), vec![Sound::Lock]);
+}
+
+#[test]
+fn clear() {
+    let garbage = TetrisUnitBlock { is_filled: true, color: Color::RGB(128, 128, 128), is_garbage: true, kind: None };
+    let empty = TetrisUnitBlock { is_filled: false, color: Color::RGB(0, 0, 0), is_garbage: false, kind: None };
+    // A bottom row the I fills.
+    let mut row = vec![garbage; 10];
+    for cell in row[3..7].iter_mut() {
+        *cell = empty;
+    }
+    let mut board = TetrisBoard::with_setup(&[row], RandomTetrisPieceGenerator::with_seed(1));
+    board.active_piece = tbp::location_to_piece(&PieceLocation {
+        kind: "I".to_string(),
+        orientation: "north".to_string(),
+        x: 4,
+        y: 10,
+    }).unwrap();
+    let mut game = Game::Marathon(board);
+    let mut tracker = tracker(&game);
+    assert_eq!(hard_drop(&mut game, &mut tracker), vec![Sound::Lock, Sound::Single]);
+}
+
+#[test]
+fn level_up() {
+    let mut game = Game::Marathon(TetrisBoard::with_seed(1));
+    let mut tracker = tracker(&game);
+    if let Game::Marathon(ref mut board) = game {
+        board.level += 1;
+    }
+    assert_eq!(tracker.update(&game), vec![Sound::LevelUp]);
+    assert!(tracker.update(&game).is_empty());
+}
+
+// Dropping every piece in the middle tops out, once.
+#[test]
+fn game_over() {
+    let mut game = Game::Marathon(TetrisBoard::with_seed(1));
+    let mut tracker = tracker(&game);
+    let mut sounds = Vec::new();
+    for _ in 0..100 {
+        sounds.extend(hard_drop(&mut game, &mut tracker));
+    }
+    assert!(game.is_finished());
+    assert_eq!(sounds.iter().filter(|&&sound| sound == Sound::GameOver).count(), 1);
+}
+
+fn marathon_at(level: u32) -> Game {
+    let mut board = TetrisBoard::with_seed(1);
+    board.level = level;
+    Game::Marathon(board)
+}
+
+#[test]
+fn music_speeds_up_with_the_level() {
+    assert_eq!(sound::music_tempo(&marathon_at(1)), 1.0);
+    assert!(sound::music_tempo(&marathon_at(5)) > sound::music_tempo(&marathon_at(4)));
+    // Up to a limit.
+    assert!(sound::music_tempo(&marathon_at(11)) > sound::music_tempo(&marathon_at(10)));
+    assert_eq!(sound::music_tempo(&marathon_at(50)), sound::music_tempo(&marathon_at(11)));
+
+    // Master speeds up a section at a time.
+    let mut master_game = MasterGame::with_seed(1);
+    assert_eq!(sound::music_tempo(&Game::Master(MasterGame::with_seed(1))), 1.0);
+    master_game.level = 99;
+    assert_eq!(sound::music_tempo(&Game::Master(master_game)), 1.0);
+    let mut master_game = MasterGame::with_seed(1);
+    master_game.level = 300;
+    assert!(sound::music_tempo(&Game::Master(master_game)) > 1.0);
+}
@@ -0,0 +1,32 @@
+extern crate rust_tetris;
+
+use rust_tetris::{PieceKind, Pos2D, TetrisPiece};
+use rust_tetris::pieces::{self, PieceSet};
+
+// Fits anywhere but at the given positions.
+fn fits_except(blocked: &[(i32, i32)]) -> Box<dyn Fn(&TetrisPiece) -> bool> {
+    let blocked = blocked.to_vec();
+    Box::new(move |piece: &TetrisPiece| !blocked.contains(&(piece.pos.x, piece.pos.y)))
+}
+
+fn position(piece: Option<TetrisPiece>) -> Option<(i32, i32)> {
+    piece.map(|piece| (piece.pos.x, piece.pos.y))
+}
+
+// The first nudge in the kick table that fits is taken. The piece set is for
+// the whole process, which is why this is a file of its own.
+#[test]
+fn srs_takes_the_first_kick_that_fits() {
+    pieces::use_piece_set(PieceSet::load("res/pieces/srs.toml").unwrap()).unwrap();
+    let t = TetrisPiece::build(PieceKind::T, Pos2D::xy(5, 5));
+    assert_eq!(position(t.rotated_right(fits_except(&[]))), Some((5, 5)));
+    assert_eq!(position(t.rotated_right(fits_except(&[(5, 5)]))), Some((4, 5)));
+    assert_eq!(position(t.rotated_right(fits_except(&[(5, 5), (4, 5)]))), Some((4, 4)));
+    assert_eq!(position(t.rotated_right(fits_except(&[(5, 5), (4, 5), (4, 4), (5, 7), (4, 7)]))), None);
+    assert_eq!(position(t.rotated_left(fits_except(&[(5, 5)]))), Some((6, 5)));
+
+    // The I has a table of its own.
+    let i = TetrisPiece::build(PieceKind::I, Pos2D::xy(5, 5));
+    assert_eq!(position(i.rotated_right(fits_except(&[(5, 5)]))), Some((3, 5)));
+    assert_eq!(position(i.rotated_left(fits_except(&[(5, 5)]))), Some((4, 5)));
+}
@@ -0,0 +1,101 @@
+extern crate rust_tetris;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_tetris::{Controller, TetrisBoard};
+use rust_tetris::tbp::{BotMessage, ExternalBot, FrontendMessage};
+
+static MOCK_BOT: &str = env!("CARGO_BIN_EXE_tbp-mock-bot");
+
+// The frontend's side of the exchange, by hand.
+#[test]
+fn mock_bot_exchange() {
+    let mut child = Command::new(MOCK_BOT).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut send = |message: &FrontendMessage| writeln!(stdin, "{}", serde_json::to_string(message).unwrap()).unwrap();
+    let mut receive = || {
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        serde_json::from_str::<BotMessage>(&line).unwrap()
+    };
+
+    match receive() {
+        BotMessage::Info { name, .. } => assert_eq!(name, "mock"),
+        message => panic!("Expected info, got {:?}", message),
+    }
+    send(&FrontendMessage::Rules { randomizer: "seven_bag".to_string() });
+    match receive() {
+        BotMessage::Ready => {}
+        message => panic!("Expected ready, got {:?}", message),
+    }
+
+    let empty_row = vec![None; 10];
+    send(&FrontendMessage::Start {
+        hold: None,
+        queue: vec!["T".to_string(), "I".to_string()],
+        combo: 0,
+        back_to_back: false,
+        board: vec![empty_row; 40],
+    });
+    send(&FrontendMessage::Suggest);
+    let piece_move = match receive() {
+        BotMessage::Suggestion { mut moves } => {
+            assert_eq!(moves.len(), 1);
+            moves.remove(0)
+        }
+        message => panic!("Expected a suggestion, got {:?}", message),
+    };
+    // Straight down onto the empty floor.
+    assert_eq!(piece_move.location.kind, "T");
+    assert_eq!(piece_move.location.orientation, "north");
+    assert_eq!(piece_move.location.y, 0);
+
+    // The next suggestion is for the next piece in the queue.
+    send(&FrontendMessage::Play { piece_move: piece_move });
+    send(&FrontendMessage::Suggest);
+    match receive() {
+        BotMessage::Suggestion { moves } => assert_eq!(moves[0].location.kind, "I"),
+        message => panic!("Expected a suggestion, got {:?}", message),
+    }
+
+    send(&FrontendMessage::Quit);
+    assert!(child.wait().unwrap().success());
+}
+
+// The frontend steering pieces to the mock bot's suggestions.
+#[test]
+fn external_bot_plays_mock_bot_moves() {
+    let mut bot = ExternalBot::spawn(&[MOCK_BOT.to_string()]).unwrap();
+    let mut board = TetrisBoard::with_seed(1);
+    let mut frames = 0;
+    while board.pieces_locked < 3 && frames < 60 * 30 {
+        let input = bot.next_input(&board);
+        board.update(&input);
+        frames += 1;
+        // Gives the bot time to answer, as the game's frames would.
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(bot.name(), "mock");
+    assert_eq!(board.pieces_locked, 3);
+    assert!(!board.is_game_over);
+}
+
+#[test]
+fn spawn_without_a_command() {
+    assert!(ExternalBot::spawn(&[]).is_err());
+    assert!(ExternalBot::spawn(&["".to_string()]).is_err());
+}
+
+// A bot that never answers quit is killed rather than waited on.
+#[test]
+fn dropping_a_stuck_bot_kills_it() {
+    let bot = ExternalBot::spawn(&["sleep".to_string(), "30".to_string()]).unwrap();
+    let started = Instant::now();
+    drop(bot);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
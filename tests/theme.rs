@@ -0,0 +1,69 @@
+extern crate rust_tetris;
+
+use std::path::Path;
+
+use rust_tetris::{Color, PieceKind};
+use rust_tetris::frame::{Block, Tile};
+use rust_tetris::theme::{self, Glyph, Glyphs, Pattern, Theme};
+
+fn block(color: Color, tile: Tile) -> Block {
+    Block { color: color, tile: tile, is_ghost: false, glow: 0 }
+}
+
+#[test]
+fn colors_replace_the_blocks_own() {
+    let theme = Theme::from_toml("background_color = [1, 2, 3]\n[colors]\nT = [10, 20, 30]\ngarbage = [40, 50, 60]\n", Path::new("")).unwrap();
+    let red = Color::RGB(255, 0, 0);
+    assert_eq!(theme.background_color, Color::RGB(1, 2, 3));
+    assert_eq!(theme.color(&block(red, Tile::Piece(PieceKind::T))), Color::RGB(10, 20, 30));
+    assert_eq!(theme.color(&block(red, Tile::Garbage)), Color::RGB(40, 50, 60));
+    // Blocks the theme leaves out keep their colors.
+    assert_eq!(theme.color(&block(red, Tile::Piece(PieceKind::I))), red);
+    assert_eq!(theme.color(&block(red, Tile::Wall)), red);
+}
+
+#[test]
+fn images_are_from_the_theme_files_folder() {
+    let theme = Theme::from_toml("background = \"sky.png\"\n[tiles]\nblock = \"block.png\"\nwall = \"walls/brick.png\"\n", Path::new("themes/mine")).unwrap();
+    let path = |file: &str| Path::new("themes/mine").join(file).to_string_lossy().into_owned();
+    assert_eq!(theme.background, Some(path("sky.png")));
+    assert_eq!(theme.tiles["block"], path("block.png"));
+    assert_eq!(theme.tiles["wall"], path("walls/brick.png"));
+
+    let bevel = Theme::named("bevel").unwrap();
+    assert!(Path::new(&bevel.tiles["block"]).is_file());
+}
+
+#[test]
+fn glyphs_mark_the_pieces() {
+    let plain = Theme::from_toml("", Path::new("")).unwrap();
+    assert!(plain.glyphs == Glyphs::None);
+    assert!(plain.glyph(PieceKind::T).is_none());
+
+    let letters = Theme::from_toml("glyphs = \"letters\"\n", Path::new("")).unwrap();
+    assert!(letters.glyph(PieceKind::T) == Some(Glyph::Letter('T')));
+
+    let patterns = Theme::from_toml("glyphs = \"patterns\"\n", Path::new("")).unwrap();
+    assert!(patterns.glyph(PieceKind::I) == Some(Glyph::Pattern(Pattern::HorizontalLine)));
+    // Pieces without a pattern get their letter.
+    assert!(patterns.glyph(PieceKind('X')) == Some(Glyph::Letter('X')));
+
+    assert!(Theme::from_toml("glyphs = \"dots\"\n", Path::new("")).is_err());
+}
+
+#[test]
+fn refuses_bad_themes() {
+    assert!(Theme::from_toml("background_color = [1, 2]\n", Path::new("")).is_err());
+    assert!(Theme::from_toml("[colors]\nT = \"red\"\n", Path::new("")).is_err());
+    assert!(Theme::named("missing").is_err());
+}
+
+#[test]
+fn names_the_themes_to_pick_from() {
+    let names = theme::names();
+    assert_eq!(names[0], "plain");
+    for name in names.iter() {
+        assert!(Theme::named(name).is_ok(), "{}", name);
+    }
+    assert!(names.contains(&"bevel".to_string()));
+}
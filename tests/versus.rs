@@ -0,0 +1,42 @@
+extern crate rust_tetris;
+
+use rust_tetris::{Color, Input, PieceKind, Pos2D, RandomTetrisPieceGenerator, TetrisBoard, TetrisPiece, TetrisUnitBlock};
+use rust_tetris::versus::VersusGame;
+
+// Four rows of garbage open on the right, with a standing I above the gap.
+// The row under them stays, so it isn't a perfect clear.
+fn tetris_ready() -> TetrisBoard {
+    let garbage = TetrisUnitBlock { is_filled: true, color: Color::RGB(128, 128, 128), is_garbage: true, kind: None };
+    let empty = TetrisUnitBlock { is_filled: false, color: Color::RGB(0, 0, 0), is_garbage: false, kind: None };
+    let mut row = vec![garbage; 10];
+    row[9] = empty;
+    let mut bottom = vec![garbage; 10];
+    bottom[0] = empty;
+    let mut rows = vec![row; 4];
+    rows.push(bottom);
+    let mut board = TetrisBoard::with_setup(&rows, RandomTetrisPieceGenerator::with_seed(1));
+    let mut i = TetrisPiece::build(PieceKind::I, Pos2D::xy(9, 5));
+    i.rotate_right();
+    board.active_piece = i;
+    board
+}
+
+// A tetris is worth 4 lines, which go against the garbage on its way in
+// before any is sent.
+#[test]
+fn attacks_cancel_incoming_garbage() {
+    for &(ref incoming, ref left, sent) in [(vec![3], vec![], 1), (vec![6], vec![2], 0), (vec![1, 1], vec![], 2)].iter() {
+        let mut versus_game = VersusGame::new();
+        versus_game.players[0].board = tetris_ready();
+        versus_game.players[0].incoming = incoming.clone();
+        let mut input = Input::new();
+        input.hard_drop_key_pressed = true;
+        versus_game.update(&[input, Input::new()]);
+
+        assert_eq!(versus_game.players[0].board.last_clear_lines, 4);
+        assert_eq!(&versus_game.players[0].incoming, left);
+        assert_eq!(versus_game.players[0].lines_sent, sent);
+        let expected: Vec<u32> = if sent > 0 { vec![sent] } else { vec![] };
+        assert_eq!(versus_game.players[1].incoming, expected);
+    }
+}